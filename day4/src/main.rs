@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Pair(Range, Range);
 
 impl Pair {
@@ -14,8 +15,19 @@ impl Pair {
     pub fn ranges_overlapping(&self) -> bool {
         self.0.overlaps_with(&self.1) || self.fully_contains()
     }
+
+    pub fn overlap(&self) -> Option<Range> {
+        self.0.intersection(&self.1)
+    }
 }
 
+impl std::fmt::Display for Pair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Timeline::new(std::slice::from_ref(self)).fmt(f)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Range {
     lower: usize,
     upper: usize,
@@ -34,6 +46,135 @@ impl Range {
         self.lower <= other.lower && self.upper >= other.lower
             || self.lower <= other.upper && self.upper >= other.upper
     }
+
+    pub fn len(&self) -> usize {
+        self.upper - self.lower + 1
+    }
+
+    pub fn intersection(&self, other: &Range) -> Option<Range> {
+        let lower = self.lower.max(other.lower);
+        let upper = self.upper.min(other.upper);
+        (lower <= upper).then(|| Range::new(lower, upper))
+    }
+}
+
+impl std::fmt::Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.lower, self.upper)
+    }
+}
+
+// Draws pairs the same way the puzzle does:
+//
+// .234.....  2-4
+// .....678.  6-8
+//
+// Sections past 9 get a ruler on top holding the higher digits, and overlapping
+// sections are marked with `^` on an extra row. Wide windows can be compressed so
+// that one column stands for several sections, `#` meaning the whole column is
+// covered and `+` only a part of it.
+struct Timeline<'a> {
+    pairs: &'a [Pair],
+    width: Option<usize>,
+}
+
+impl<'a> Timeline<'a> {
+    pub fn new(pairs: &'a [Pair]) -> Self {
+        Timeline { pairs, width: None }
+    }
+
+    pub fn compress(mut self, width: usize) -> Self {
+        self.width = Some(width.max(1));
+        self
+    }
+
+    fn span(&self) -> Range {
+        let ranges = self.pairs.iter().flat_map(|pair| [pair.0, pair.1]);
+        let lower = ranges.clone().map(|range| range.lower).min().unwrap_or(1).min(1);
+        let upper = ranges.map(|range| range.upper).max().unwrap_or(1).max(1);
+        Range::new(lower, upper)
+    }
+
+    fn scale(&self, span: &Range) -> usize {
+        match self.width {
+            Some(width) if span.len() > width => span.len().div_ceil(width),
+            _ => 1,
+        }
+    }
+
+    fn columns(&self) -> Vec<Range> {
+        let span = self.span();
+        let scale = self.scale(&span);
+        (span.lower..=span.upper)
+            .step_by(scale)
+            .map(|lower| Range::new(lower, (lower + scale - 1).min(span.upper)))
+            .collect()
+    }
+
+    fn ruler(columns: &[Range]) -> Vec<String> {
+        let upper = columns.last().map_or(0, |column| column.upper);
+        let mut rows = Vec::new();
+        let mut power = 10;
+
+        while upper >= power {
+            let row: String = columns.iter()
+                .map(|column| match column.lower >= power {
+                    true => char::from_digit((column.lower / power % 10) as u32, 10).unwrap(),
+                    false => ' ',
+                })
+                .collect();
+            rows.insert(0, row);
+            power *= 10;
+        }
+
+        rows
+    }
+
+    fn row(columns: &[Range], range: &Range) -> String {
+        let cells: String = columns.iter()
+            .map(|column| match range.intersection(column) {
+                Some(part) if column.len() == 1 => char::from_digit((part.lower % 10) as u32, 10).unwrap(),
+                Some(part) if part == *column => '#',
+                Some(_) => '+',
+                None => '.',
+            })
+            .collect();
+        format!("{}  {}", cells, range)
+    }
+
+    fn overlap_row(columns: &[Range], overlap: &Range) -> String {
+        let cells: String = columns.iter()
+            .map(|column| if overlap.intersection(column).is_some() { '^' } else { ' ' })
+            .collect();
+        format!("{}  overlap {}", cells, overlap)
+    }
+}
+
+impl std::fmt::Display for Timeline<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns = self.columns();
+        let mut lines = Vec::new();
+
+        match columns.first() {
+            Some(column) if column.len() > 1 => {
+                lines.push(format!("{} sections per column from {}", column.len(), column.lower));
+            }
+            _ => lines.extend(Self::ruler(&columns)),
+        }
+
+        for (i, pair) in self.pairs.iter().enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            lines.push(Self::row(&columns, &pair.0));
+            lines.push(Self::row(&columns, &pair.1));
+            if let Some(overlap) = pair.overlap() {
+                lines.push(Self::overlap_row(&columns, &overlap));
+            }
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
 }
 
 fn parse_range(slice: &str) -> Range {
@@ -43,6 +184,25 @@ fn parse_range(slice: &str) -> Range {
     Range::new(lower, upper)
 }
 
+// Reads the pairs back out of a rendered `Timeline`, only the `cells  lower-upper`
+// rows are taken into account.
+fn parse_timeline(text: &str) -> Vec<Pair> {
+    let ranges: Vec<Range> = text.lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts[..] {
+                [_, label] => {
+                    let (lower, upper) = label.split_once('-')?;
+                    Some(Range::new(lower.parse().ok()?, upper.parse().ok()?))
+                }
+                _ => None,
+            }
+        })
+        .collect();
+
+    ranges.chunks(2).map(|pair| Pair::new(pair[0], pair[1])).collect()
+}
+
 fn parse_pair(line: &str) -> Pair {
     if let Some(i) = line.find(",") {
         let first_part = &line[..i];
@@ -55,6 +215,103 @@ fn parse_pair(line: &str) -> Pair {
     }
 }
 
+fn parse_pairs(input: &str) -> Vec<Pair> {
+    input.lines().filter(|line| !line.trim().is_empty()).map(parse_pair).collect()
+}
+
+// Every elf gets an id, the first elf of pair `n` is `2 * n` and the second one is `2 * n + 1`.
+fn elves(pairs: &[Pair]) -> Vec<Range> {
+    pairs.iter().flat_map(|pair| [pair.0, pair.1]).collect()
+}
+
+// Elves whose range is fully contained in some other elf's range.
+// Out of identical ranges only the one with the lowest id is kept.
+fn releasable(pairs: &[Pair]) -> Vec<usize> {
+    let elves = elves(pairs);
+    let mut order: Vec<usize> = (0..elves.len()).collect();
+    order.sort_by_key(|&i| (elves[i].lower, std::cmp::Reverse(elves[i].upper), i));
+
+    let mut released = Vec::new();
+    let mut reach: Option<usize> = None;
+    for i in order {
+        match reach {
+            Some(upper) if elves[i].upper <= upper => released.push(i),
+            _ => reach = Some(elves[i].upper),
+        }
+    }
+
+    released.sort_unstable();
+    released
+}
+
+// Minimum set of elves covering every section of `target`, greedy version.
+// Always extends the covered span with the range that reaches the furthest.
+fn min_cover_greedy(pairs: &[Pair], target: Range) -> Option<Vec<usize>> {
+    let elves = elves(pairs);
+    let mut order: Vec<usize> = (0..elves.len()).collect();
+    order.sort_by_key(|&i| elves[i].lower);
+
+    let mut chosen = Vec::new();
+    let mut next = target.lower;
+    let mut i = 0;
+
+    while next <= target.upper {
+        let mut best: Option<usize> = None;
+        while i < order.len() && elves[order[i]].lower <= next {
+            let elf = order[i];
+            if elves[elf].upper >= next && best.is_none_or(|b| elves[elf].upper > elves[b].upper) {
+                best = Some(elf);
+            }
+            i += 1;
+        }
+
+        let best = best?;
+        chosen.push(best);
+        next = elves[best].upper + 1;
+    }
+
+    chosen.sort_unstable();
+    Some(chosen)
+}
+
+// Minimum set of elves covering every section of `target`, exhaustive version.
+// Breadth first search over the covered prefix, so the first full cover found is optimal.
+fn min_cover_optimal(pairs: &[Pair], target: Range) -> Option<Vec<usize>> {
+    use std::collections::{hash_map::Entry, HashMap, VecDeque};
+
+    let elves = elves(pairs);
+    // Covered prefix (exclusive end) -> (previous prefix, elf used to get here).
+    let mut visited: HashMap<usize, Option<(usize, usize)>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    visited.insert(target.lower, None);
+    queue.push_back(target.lower);
+
+    while let Some(next) = queue.pop_front() {
+        if next > target.upper {
+            let mut chosen = Vec::new();
+            let mut at = next;
+            while let Some(Some((prev, elf))) = visited.get(&at) {
+                chosen.push(*elf);
+                at = *prev;
+            }
+            chosen.sort_unstable();
+            return Some(chosen);
+        }
+
+        for (elf, range) in elves.iter().enumerate() {
+            if range.lower <= next && range.upper >= next {
+                let reach = range.upper + 1;
+                if let Entry::Vacant(entry) = visited.entry(reach) {
+                    entry.insert(Some((next, elf)));
+                    queue.push_back(reach);
+                }
+            }
+        }
+    }
+
+    None
+}
+
 // Part 1
 pub fn how_many_pairs_1(input: &str) -> usize {
     let mut result = 0;
@@ -79,7 +336,117 @@ pub fn how_many_pairs_2(input: &str) -> usize {
     result
 }
 
+// Lines `from..to` of the input (1-based, inclusive), a single number selects one line.
+// Line 0 is read as line 1, a window ending before it starts is refused.
+fn parse_window(arg: &str) -> Result<(usize, usize), String> {
+    let (from, to) = arg.split_once("..").unwrap_or((arg, arg));
+    let from = from.parse::<usize>().map_err(|_| format!("Invalid window '{}'.", arg))?;
+    let to = to.parse::<usize>().map_err(|_| format!("Invalid window '{}'.", arg))?;
+    if to < from {
+        return Err(format!("Invalid window '{}', it ends before it starts.", arg));
+    }
+    let from = from.max(1);
+    Ok((from, to.max(from)))
+}
+
 fn main() {
     let input = std::fs::read_to_string("input.txt").expect("Invalid filename.");
-    println!("{}", how_many_pairs_2(&input));
-}
\ No newline at end of file
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = |name: &str| args.iter().position(|arg| arg == name).map(|i| args.get(i + 1).expect("Missing flag value."));
+
+    if let Some(window) = flag("--render") {
+        let (from, to) = match parse_window(window) {
+            Ok(window) => window,
+            Err(message) => {
+                eprintln!("{}", message);
+                return;
+            }
+        };
+        let pairs: Vec<Pair> = input.lines().skip(from - 1).take(to - from + 1).map(parse_pair).collect();
+        let timeline = Timeline::new(&pairs);
+        match flag("--width") {
+            Some(width) => println!("{}", timeline.compress(width.parse().expect("Invalid width."))),
+            None => println!("{}", timeline),
+        }
+    } else {
+        println!("{}", how_many_pairs_2(&input));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{min_cover_greedy, min_cover_optimal, parse_pairs, parse_timeline, parse_window, releasable, Range, Timeline};
+
+    #[test]
+    fn releasable_elves() {
+        let pairs = parse_pairs("2-8,3-7\n6-6,4-6\n2-4,6-8\n3-7,9-9");
+        assert_eq!(releasable(&pairs), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn min_cover() {
+        let pairs = parse_pairs("2-4,6-8\n2-3,4-5\n5-7,7-9\n2-8,3-7");
+        let target = Range::new(2, 9);
+        assert_eq!(min_cover_greedy(&pairs, target), Some(vec![5, 6]));
+        assert_eq!(min_cover_optimal(&pairs, target).map(|cover| cover.len()), Some(2));
+        assert_eq!(min_cover_greedy(&pairs, Range::new(1, 9)), None);
+        assert_eq!(min_cover_optimal(&pairs, Range::new(1, 9)), None);
+    }
+
+    #[test]
+    fn min_cover_input() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let pairs = parse_pairs(&input);
+        let target = Range::new(1, 99);
+        let greedy = min_cover_greedy(&pairs, target).unwrap();
+        let optimal = min_cover_optimal(&pairs, target).unwrap();
+        assert_eq!(greedy.len(), optimal.len());
+    }
+
+    #[test]
+    fn render_example() {
+        let pairs = parse_pairs("2-4,6-8\n2-3,4-5\n5-7,7-9\n2-8,3-7\n6-6,4-6\n2-6,4-8");
+        let rendered = Timeline::new(&pairs).to_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], ".234.....  2-4");
+        assert_eq!(lines[1], ".....678.  6-8");
+        assert_eq!(lines[3], ".23......  2-3");
+        assert_eq!(lines[4], "...45....  4-5");
+        assert_eq!(lines[7], "......789  7-9");
+        assert_eq!(lines[8], "      ^    overlap 7-7");
+        assert_eq!(pairs[0].to_string(), ".234....  2-4\n.....678  6-8");
+    }
+
+    #[test]
+    fn render_round_trip() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let pairs = parse_pairs(&input);
+
+        for window in pairs.chunks(50) {
+            let rendered = Timeline::new(window).to_string();
+            assert_eq!(parse_timeline(&rendered), window);
+            assert!(rendered.starts_with(&format!("{}{}{}", " ".repeat(9), "1".repeat(10), "2".repeat(10))));
+
+            for line in rendered.lines().filter(|line| line.split_whitespace().count() == 2) {
+                let cells = line.split_whitespace().next().unwrap();
+                let range = parse_timeline(&format!("{line}\n{line}"))[0].0;
+                let first = cells.find(|c: char| c != '.').unwrap() + 1;
+                let last = cells.rfind(|c: char| c != '.').unwrap() + 1;
+                assert_eq!((first, last), (range.lower, range.upper));
+            }
+
+            let compressed = Timeline::new(window).compress(20).to_string();
+            assert_eq!(parse_timeline(&compressed), window);
+            assert!(compressed.lines().skip(1).all(|line| line.len() <= 20 + 2 + "overlap 99-99".len()));
+        }
+    }
+    #[test]
+    fn render_windows() {
+        assert_eq!(parse_window("3"), Ok((3, 3)));
+        assert_eq!(parse_window("2..5"), Ok((2, 5)));
+        assert_eq!(parse_window("0"), Ok((1, 1)));
+        assert_eq!(parse_window("0..4"), Ok((1, 4)));
+        assert_eq!(parse_window("3..1"), Err("Invalid window '3..1', it ends before it starts.".to_string()));
+        assert_eq!(parse_window("x..1"), Err("Invalid window 'x..1'.".to_string()));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{Crate, Move, Supplies};
+
+// A crane model decides how a `move n from a to b` instruction is carried out.
+pub trait Crane {
+    fn kind(&self) -> Move;
+
+    // Moves up to `amount` crates, returns the moved crates top first as they were taken.
+    fn lift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate>;
+
+    // Undoes `lift(amount, from, to)`, the crates go from `to` back onto `from`.
+    fn unlift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate>;
+}
+
+// The crane carrying out `move` instructions and the one carrying out `ordmove`.
+#[derive(Clone, Copy)]
+pub struct Cranes<'a> {
+    pub moves: &'a dyn Crane,
+    pub ordmoves: &'a dyn Crane,
+}
+
+impl<'a> Cranes<'a> {
+    // A single model for both verbs, like one picked on the command line.
+    pub fn only(crane: &'a dyn Crane) -> Self {
+        Cranes { moves: crane, ordmoves: crane }
+    }
+}
+
+// On its own a crane carries out `move`, `ordmove` stays with the CrateMover 9001.
+impl<'a, C: Crane + 'a> From<&'a C> for Cranes<'a> {
+    fn from(crane: &'a C) -> Self {
+        Cranes { moves: crane, ordmoves: &CrateMover9001 }
+    }
+}
+
+impl<'a> From<&'a dyn Crane> for Cranes<'a> {
+    fn from(crane: &'a dyn Crane) -> Self {
+        Cranes { moves: crane, ordmoves: &CrateMover9001 }
+    }
+}
+
+// Moves crates one at a time, so they end up in reverse order.
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn kind(&self) -> Move {
+        Move::Default
+    }
+
+    fn lift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate> {
+        Supplies::move_default(supplies, amount, from, to)
+    }
+
+    fn unlift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate> {
+        Supplies::move_default(supplies, amount, to, from)
+    }
+}
+
+// Moves all the crates at once, keeping their order.
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn kind(&self) -> Move {
+        Move::OrdMove
+    }
+
+    fn lift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate> {
+        Supplies::move_ord(supplies, amount, from, to)
+    }
+
+    fn unlift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate> {
+        Supplies::move_ord(supplies, amount, to, from)
+    }
+}
+
+// Grabs at most `capacity` crates at once, each grab keeps its order. A capacity of 0 grabs one.
+pub struct LimitedCrane {
+    pub capacity: usize,
+}
+
+impl Crane for LimitedCrane {
+    fn kind(&self) -> Move {
+        Move::Capacity(self.capacity)
+    }
+
+    fn lift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate> {
+        let mut moved = Vec::with_capacity(amount);
+
+        while moved.len() < amount {
+            let grab = (amount - moved.len()).min(self.capacity.max(1));
+            let package = Supplies::move_ord(supplies, grab, from, to);
+            if package.is_empty() {
+                break;
+            }
+            moved.extend(package);
+        }
+
+        moved
+    }
+
+    // The last grab ended up on top, so it has to be taken back first.
+    fn unlift(&self, supplies: &mut Supplies, amount: usize, from: usize, to: usize) -> Vec<Crate> {
+        let capacity = self.capacity.max(1);
+        let mut grabs = vec![capacity; amount / capacity];
+        if !amount.is_multiple_of(capacity) {
+            grabs.push(amount % capacity);
+        }
+
+        grabs.into_iter().rev().flat_map(|grab| Supplies::move_ord(supplies, grab, to, from)).collect()
+    }
+}
+
+impl Move {
+    pub fn crane(self) -> Box<dyn Crane> {
+        match self {
+            Move::Default => Box::new(CrateMover9000),
+            Move::OrdMove => Box::new(CrateMover9001),
+            Move::Capacity(capacity) => Box::new(LimitedCrane { capacity }),
+        }
+    }
+}
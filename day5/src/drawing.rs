@@ -0,0 +1,148 @@
+use std::fmt;
+
+use crate::error::DrawingError;
+use crate::{Stack, Supplies};
+
+fn is_index_line(line: &str) -> bool {
+    let mut parts = line.split_whitespace().peekable();
+    parts.peek().is_some() && parts.all(|part| part.parse::<usize>().is_ok())
+}
+
+// The line holding the stack numbers or names, the drawing is everything above it.
+// Names are only recognized right below the crates, numbers anywhere.
+pub fn index_line(lines: &[&str]) -> Option<usize> {
+    let rows = lines.iter().take_while(|line| line.contains('[')).count();
+    match lines.get(rows) {
+        Some(line) if rows > 0 && !line.trim().is_empty() => Some(rows),
+        _ => lines.iter().position(|line| is_index_line(line)),
+    }
+}
+
+// Either exactly 1, 2, 3, ... or unique names that aren't numbers.
+fn valid_names(names: &[String]) -> bool {
+    let numbered = names.iter().enumerate().all(|(i, name)| *name == (i + 1).to_string());
+    let unique = names.iter().enumerate().all(|(i, name)| !names[..i].contains(name));
+    numbered || unique && names.iter().all(|name| name.parse::<usize>().is_err())
+}
+
+// Character positions (start, end exclusive).
+type Span = (usize, usize);
+
+// Spans of the whitespace separated words of a line.
+fn spans(chars: &[char]) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut start = None;
+
+    for (i, c) in chars.iter().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, chars.len()));
+    }
+
+    spans
+}
+
+// Crates of one drawing row as (span, label), `line` is only used for errors.
+fn crates(chars: &[char], line: usize) -> Result<Vec<(Span, String)>, DrawingError> {
+    let mut crates = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '[' => {
+                let close = chars[i..].iter().position(|c| *c == ']')
+                    .ok_or(DrawingError::Unclosed { line, column: i + 1 })?;
+                let label: String = chars[i + 1..i + close].iter().collect();
+                if label.trim().is_empty() || label.contains('[') {
+                    return Err(DrawingError::BadLabel { line, column: i + 1 });
+                }
+                crates.push(((i, i + close + 1), label));
+                i += close + 1;
+            }
+            _ => return Err(DrawingError::StrayText { line, column: i + 1 }),
+        }
+    }
+
+    Ok(crates)
+}
+
+impl Supplies {
+    // Reads the stack drawing at the start of `input`. Stacks are located through the
+    // number line, so crate labels may be wider than one character and there may be
+    // any number of stacks as long as every crate sits above exactly one number.
+    // Instead of numbers the stacks can be given names.
+    pub fn parse(input: &str) -> Result<Self, DrawingError> {
+        let lines: Vec<&str> = input.lines().collect();
+        let index = index_line(&lines).ok_or(DrawingError::MissingIndexLine)?;
+
+        let numbers: Vec<char> = lines[index].chars().collect();
+        let columns = spans(&numbers);
+        let names: Vec<String> = columns.iter().map(|(s, e)| numbers[*s..*e].iter().collect()).collect();
+        if !valid_names(&names) {
+            return Err(DrawingError::BadIndexLine { line: index + 1 });
+        }
+
+        let mut supplies = Supplies::from(columns.len());
+        supplies.names = names;
+
+        for (i, line) in lines[..index].iter().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let chars: Vec<char> = line.chars().collect();
+            let mut row = vec![None; columns.len()];
+
+            for ((start, end), label) in crates(&chars, i + 1)? {
+                let mut hit = columns.iter().enumerate().filter(|(_, (s, e))| start < *e && *s < end);
+                match (hit.next(), hit.next()) {
+                    (Some((idx, _)), None) if row[idx].is_none() => row[idx] = Some(label),
+                    _ => return Err(DrawingError::Misaligned { line: i + 1, column: start + 1 }),
+                }
+            }
+
+            for (idx, label) in row.into_iter().enumerate() {
+                match label {
+                    Some(label) => supplies.push_crate_back(idx, label),
+                    None if supplies.stacks[idx].len() > 0 => {
+                        return Err(DrawingError::Floating { line: i, stack: idx + 1 });
+                    }
+                    None => (),
+                }
+            }
+        }
+
+        Ok(supplies)
+    }
+}
+
+// Draws the stacks exactly like the puzzle input does, crates in `[A]` slots
+// separated by a single space and the stack numbers underneath. Slots widen to
+// fit the longest label or stack name.
+impl fmt::Display for Supplies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self.stacks.iter().map(Stack::len).max().unwrap_or(0);
+        let label = self.stacks.iter().flat_map(|stack| stack.crates.iter()).map(|c| c.chars().count()).max();
+        let name = self.names.iter().map(|name| name.chars().count()).max();
+        let width = (label.unwrap_or(1) + 2).max(name.unwrap_or(1));
+
+        for row in 0..height {
+            let slots: Vec<String> = self.stacks
+                .iter()
+                .map(|stack| match (row + stack.len()).checked_sub(height) {
+                    Some(depth) => format!("{:^width$}", format!("[{}]", stack.crates[depth])),
+                    None => " ".repeat(width),
+                })
+                .collect();
+            writeln!(f, "{}", slots.join(" "))?;
+        }
+
+        let numbers: Vec<String> = self.names.iter().map(|name| format!("{:^width$}", name)).collect();
+        write!(f, "{}", numbers.join(" "))
+    }
+}
@@ -0,0 +1,255 @@
+use crate::crane::Cranes;
+use crate::error::{self, InstructionError};
+use crate::instruction::Instruction;
+use crate::script::{Machine, Script};
+use crate::{Crate, Mode, Move, Stack, Supplies};
+
+type Link = Option<usize>;
+
+// Node of an implicit treap, the position of a crate is given by the sizes of the
+// subtrees to its left. `reversed` is a pending flip of the whole subtree.
+struct Node {
+    label: Crate,
+    priority: u64,
+    left: Link,
+    right: Link,
+    size: usize,
+    reversed: bool,
+}
+
+// Stack engine for big inputs. Every stack is a treap ordered top first, so taking the
+// top `n` crates, flipping them and putting them on another stack are all O(log n)
+// no matter how many crates are moved. That covers `move`, `ordmove`, `swap` and `reverse`,
+// a crane with a capacity `k` below `n` still needs one take and put per grab, O(n / k · log n).
+// `Supplies` stays the reference implementation.
+pub struct Rope {
+    nodes: Vec<Node>,
+    stacks: Vec<Link>,
+    names: Vec<String>,
+    seed: u64,
+}
+
+impl Rope {
+    pub fn from(supplies: &Supplies) -> Self {
+        let mut rope = Rope {
+            nodes: Vec::new(),
+            stacks: Vec::with_capacity(supplies.size()),
+            names: supplies.names.clone(),
+            seed: 0x2545_f491_4f6c_dd1d,
+        };
+
+        for stack in supplies.stacks.iter() {
+            let mut root = None;
+            for label in stack.crates.iter() {
+                let node = rope.node(label.clone());
+                root = rope.merge(root, Some(node));
+            }
+            rope.stacks.push(root);
+        }
+
+        rope
+    }
+
+    pub fn to_supplies(&self) -> Supplies {
+        let mut stacks = Vec::with_capacity(self.stacks.len());
+        for idx in 0..self.stacks.len() {
+            let mut crates = Vec::with_capacity(self.len(idx));
+            self.collect(self.stacks[idx], false, &mut crates);
+            stacks.push(Stack { crates: crates.into() });
+        }
+        Supplies { stacks, names: self.names.clone() }
+    }
+
+    pub fn size(&self) -> usize {
+        self.stacks.len()
+    }
+
+    pub fn len(&self, idx: usize) -> usize {
+        self.subtree(self.stacks[idx])
+    }
+
+    pub fn on_top(&self) -> String {
+        let mut buffer = String::with_capacity(self.stacks.len());
+
+        for stack in self.stacks.iter() {
+            if let Some(label) = self.first(*stack, false) {
+                buffer.push_str(label);
+            }
+        }
+
+        buffer
+    }
+
+    // Lenient execution like `Supplies::read_instruction_with`, moves stop once the source runs out.
+    pub fn apply<'a>(&mut self, instruction: &Instruction, cranes: impl Into<Cranes<'a>>) {
+        let cranes = cranes.into();
+        match *instruction {
+            Instruction::Move { amount, from, to } => self.move_crates(amount, from, to, cranes.moves.kind()),
+            Instruction::OrdMove { amount, from, to } => self.move_crates(amount, from, to, cranes.ordmoves.kind()),
+            Instruction::Swap { a, b } => self.stacks.swap(a, b),
+            Instruction::Reverse { amount, stack } => {
+                let top = self.take(stack, amount);
+                let top = self.reverse(top);
+                self.put(stack, top);
+            }
+        }
+    }
+
+    // Executes the script following the drawing.
+    pub fn run<'a>(&mut self, input: &str, cranes: impl Into<Cranes<'a>>) -> error::Result<()> {
+        let script = Script::parse(Supplies::instruction_lines(input), &self.names, Mode::Lenient)?;
+        script.run(&mut RopeRunner { rope: self, cranes: cranes.into() })
+    }
+
+    pub fn move_crates(&mut self, amount: usize, from: usize, to: usize, kind: Move) {
+        // Taking crates off a stack and putting them right back changes nothing.
+        if from == to {
+            return;
+        }
+
+        match kind {
+            Move::Default => {
+                let top = self.take(from, amount);
+                let top = self.reverse(top);
+                self.put(to, top);
+            }
+            Move::OrdMove => {
+                let top = self.take(from, amount);
+                self.put(to, top);
+            }
+            // One crate per grab is the CrateMover 9000, a single grab keeps the order.
+            Move::Capacity(capacity) if capacity <= 1 => self.move_crates(amount, from, to, Move::Default),
+            Move::Capacity(capacity) if capacity >= amount.min(self.len(from)) => {
+                self.move_crates(amount, from, to, Move::OrdMove)
+            }
+            // Grabs land in reverse order with each grab's order kept, which one flip can't express.
+            Move::Capacity(capacity) => {
+                let mut left = amount.min(self.len(from));
+                while left > 0 {
+                    let top = self.take(from, left.min(capacity));
+                    left -= self.subtree(top);
+                    self.put(to, top);
+                }
+            }
+        }
+    }
+
+    fn take(&mut self, idx: usize, amount: usize) -> Link {
+        let (top, rest) = self.split(self.stacks[idx], amount);
+        self.stacks[idx] = rest;
+        top
+    }
+
+    fn put(&mut self, idx: usize, top: Link) {
+        self.stacks[idx] = self.merge(top, self.stacks[idx]);
+    }
+
+    fn node(&mut self, label: Crate) -> usize {
+        // xorshift, priorities only have to look random.
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.nodes.push(Node { label, priority: self.seed, left: None, right: None, size: 1, reversed: false });
+        self.nodes.len() - 1
+    }
+
+    fn subtree(&self, link: Link) -> usize {
+        link.map_or(0, |node| self.nodes[node].size)
+    }
+
+    fn update(&mut self, node: usize) {
+        self.nodes[node].size = 1 + self.subtree(self.nodes[node].left) + self.subtree(self.nodes[node].right);
+    }
+
+    fn reverse(&mut self, link: Link) -> Link {
+        if let Some(node) = link {
+            self.nodes[node].reversed ^= true;
+        }
+        link
+    }
+
+    fn push_down(&mut self, node: usize) {
+        if self.nodes[node].reversed {
+            let Node { left, right, .. } = self.nodes[node];
+            self.nodes[node].left = self.reverse(right);
+            self.nodes[node].right = self.reverse(left);
+            self.nodes[node].reversed = false;
+        }
+    }
+
+    // Everything in `a` ends up above everything in `b`.
+    fn merge(&mut self, a: Link, b: Link) -> Link {
+        match (a, b) {
+            (None, link) | (link, None) => link,
+            (Some(x), Some(y)) if self.nodes[x].priority > self.nodes[y].priority => {
+                self.push_down(x);
+                self.nodes[x].right = self.merge(self.nodes[x].right, b);
+                self.update(x);
+                Some(x)
+            }
+            (Some(_), Some(y)) => {
+                self.push_down(y);
+                self.nodes[y].left = self.merge(a, self.nodes[y].left);
+                self.update(y);
+                Some(y)
+            }
+        }
+    }
+
+    // The first `amount` crates and the rest.
+    fn split(&mut self, link: Link, amount: usize) -> (Link, Link) {
+        let Some(node) = link else { return (None, None) };
+        self.push_down(node);
+
+        let left = self.subtree(self.nodes[node].left);
+        if amount <= left {
+            let (a, b) = self.split(self.nodes[node].left, amount);
+            self.nodes[node].left = b;
+            self.update(node);
+            (a, Some(node))
+        } else {
+            let (a, b) = self.split(self.nodes[node].right, amount - left - 1);
+            self.nodes[node].right = a;
+            self.update(node);
+            (Some(node), b)
+        }
+    }
+
+    // Reading doesn't push the flags down, `flipped` tracks the ones met on the way.
+    fn children(&self, node: usize, flipped: bool) -> (Link, Link, bool) {
+        let Node { left, right, reversed, .. } = self.nodes[node];
+        let flipped = flipped ^ reversed;
+        if flipped { (right, left, flipped) } else { (left, right, flipped) }
+    }
+
+    fn first(&self, link: Link, flipped: bool) -> Option<&Crate> {
+        let node = link?;
+        let (left, _, flipped) = self.children(node, flipped);
+        self.first(left, flipped).or(Some(&self.nodes[node].label))
+    }
+
+    fn collect(&self, link: Link, flipped: bool, out: &mut Vec<Crate>) {
+        if let Some(node) = link {
+            let (left, right, flipped) = self.children(node, flipped);
+            self.collect(left, flipped, out);
+            out.push(self.nodes[node].label.clone());
+            self.collect(right, flipped, out);
+        }
+    }
+}
+
+struct RopeRunner<'a> {
+    rope: &'a mut Rope,
+    cranes: Cranes<'a>,
+}
+
+impl Machine for RopeRunner<'_> {
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), InstructionError> {
+        self.rope.apply(instruction, self.cranes);
+        Ok(())
+    }
+
+    fn on_top(&self) -> String {
+        self.rope.on_top()
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InstructionError {
+    // Stacks are numbered from 1 like in the drawing.
+    Underflow { stack: usize, needed: usize, available: usize },
+    BadStackIndex(usize),
+    Malformed(String),
+    UnknownVerb(String),
+    UnknownStack(String),
+    UnexpectedBrace,
+    UnclosedBlock,
+    // `repeat`, `}` and `assert` read one line at a time instead of as part of a script.
+    ScriptOnly(String),
+    AssertionFailed { expected: String, found: String },
+}
+
+impl fmt::Display for InstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionError::Underflow { stack, needed, available } => {
+                write!(f, "stack {} holds {} crates, {} needed", stack, available, needed)
+            }
+            InstructionError::BadStackIndex(idx) => write!(f, "there is no stack {}", idx),
+            InstructionError::Malformed(ins) => write!(f, "malformed instruction '{}'", ins),
+            InstructionError::UnknownVerb(verb) => write!(f, "unknown instruction '{}'", verb),
+            InstructionError::UnknownStack(name) => write!(f, "there is no stack named '{}'", name),
+            InstructionError::UnexpectedBrace => write!(f, "'}}' without a matching 'repeat'"),
+            InstructionError::UnclosedBlock => write!(f, "'repeat' block is never closed"),
+            InstructionError::ScriptOnly(line) => write!(f, "'{}' only works in a script", line),
+            InstructionError::AssertionFailed { expected, found } => {
+                write!(f, "expected \"{}\" on top, found \"{}\"", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstructionError {}
+
+// Lines and columns count from 1.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DrawingError {
+    MissingIndexLine,
+    BadIndexLine { line: usize },
+    Unclosed { line: usize, column: usize },
+    BadLabel { line: usize, column: usize },
+    StrayText { line: usize, column: usize },
+    Misaligned { line: usize, column: usize },
+    Floating { line: usize, stack: usize },
+}
+
+impl fmt::Display for DrawingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrawingError::MissingIndexLine => write!(f, "the drawing has no stack number line"),
+            DrawingError::BadIndexLine { line } => {
+                write!(f, "line {}: stacks have to be numbered 1, 2, 3, ... or have unique names", line)
+            }
+            DrawingError::Unclosed { line, column } => write!(f, "line {}:{}: crate is missing ']'", line, column),
+            DrawingError::BadLabel { line, column } => write!(f, "line {}:{}: invalid crate label", line, column),
+            DrawingError::StrayText { line, column } => write!(f, "line {}:{}: text outside of a crate", line, column),
+            DrawingError::Misaligned { line, column } => {
+                write!(f, "line {}:{}: crate is not above exactly one stack number", line, column)
+            }
+            DrawingError::Floating { line, stack } => write!(f, "line {}: crate floating above stack {}", line, stack),
+        }
+    }
+}
+
+impl std::error::Error for DrawingError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,
+    pub kind: InstructionError,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
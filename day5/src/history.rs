@@ -0,0 +1,160 @@
+use std::fmt;
+
+use crate::crane::{Crane, Cranes, CrateMover9000, CrateMover9001};
+use crate::error::{self, InstructionError};
+use crate::{Crate, Mode, Move, Supplies};
+
+// One executed instruction. Moved `crates` are listed top first as they were taken from `from`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Move { from: usize, to: usize, crates: Vec<Crate>, kind: Move },
+    Swap { a: usize, b: usize },
+    Reverse { stack: usize, amount: usize },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Move { from, to, crates, kind: Move::OrdMove } => {
+                write!(f, "ordmove {} from {} to {}", crates.len(), from + 1, to + 1)
+            }
+            Step::Move { from, to, crates, .. } => write!(f, "move {} from {} to {}", crates.len(), from + 1, to + 1),
+            Step::Swap { a, b } => write!(f, "swap {} {}", a + 1, b + 1),
+            Step::Reverse { stack, amount } => write!(f, "reverse {} from {}", amount, stack + 1),
+        }
+    }
+}
+
+impl Supplies {
+    // Puts the crates of an executed step back where they were taken from.
+    fn revert(&mut self, step: &Step) {
+        match step {
+            Step::Move { from, to, crates, .. } => {
+                for _ in 0..crates.len() {
+                    self.pop_crate_top(*to);
+                }
+                for val in crates.iter().rev() {
+                    self.push_crate_top(*from, val.clone());
+                }
+            }
+            Step::Swap { .. } | Step::Reverse { .. } => self.replay(step),
+        }
+    }
+
+    fn replay(&mut self, step: &Step) {
+        match step {
+            Step::Move { from, to, crates, kind } => {
+                self.move_crates(crates.len(), *from, *to, &*kind.crane());
+            }
+            Step::Swap { a, b } => {
+                self.swap_stacks(*a, *b);
+            }
+            Step::Reverse { stack, amount } => {
+                self.reverse_top(*amount, *stack);
+            }
+        }
+    }
+}
+
+// Records every instruction executed on the stacks, steps past `position` are
+// the ones that were undone and can still be redone.
+pub struct History {
+    initial: Supplies,
+    current: Supplies,
+    steps: Vec<Step>,
+    position: usize,
+    // Carrying out `move` and `ordmove`, see `Cranes`.
+    moves: Box<dyn Crane>,
+    ordmoves: Box<dyn Crane>,
+}
+
+impl History {
+    pub fn new(supplies: Supplies) -> Self {
+        Self::with_crane(supplies, Box::new(CrateMover9000))
+    }
+
+    // `ordmove` stays with the CrateMover 9001.
+    pub fn with_crane(supplies: Supplies, crane: Box<dyn Crane>) -> Self {
+        Self::with_cranes(supplies, crane, Box::new(CrateMover9001))
+    }
+
+    pub fn with_cranes(supplies: Supplies, moves: Box<dyn Crane>, ordmoves: Box<dyn Crane>) -> Self {
+        History { initial: supplies.clone(), current: supplies, steps: Vec::new(), position: 0, moves, ordmoves }
+    }
+
+    // Runs the whole script following the drawing in `input`, blocks and asserts included,
+    // and records the steps it executed.
+    pub fn run(input: &str, moves: Box<dyn Crane>, ordmoves: Box<dyn Crane>, mode: Mode) -> error::Result<Self> {
+        let initial = Supplies::new(input);
+        let mut current = initial.clone();
+        let steps = current.run(input, Cranes { moves: &*moves, ordmoves: &*ordmoves }, mode)?;
+        let position = steps.len();
+        Ok(History { initial, current, steps, position, moves, ordmoves })
+    }
+
+    pub fn current(&self) -> &Supplies {
+        &self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps[..self.position]
+    }
+
+    // Executing a new instruction drops everything that could have been redone.
+    pub fn read_instruction(&mut self, instruction: &str) -> Result<Option<&Step>, InstructionError> {
+        let cranes = Cranes { moves: &*self.moves, ordmoves: &*self.ordmoves };
+        let Some(step) = self.current.read_instruction_with(instruction, cranes)? else {
+            return Ok(None);
+        };
+        self.steps.truncate(self.position);
+        self.steps.push(step);
+        self.position += 1;
+        Ok(self.steps.last())
+    }
+
+    pub fn undo(&mut self) -> Option<&Step> {
+        self.position = self.position.checked_sub(1)?;
+        let step = &self.steps[self.position];
+        self.current.revert(step);
+        Some(step)
+    }
+
+    pub fn redo(&mut self) -> Option<&Step> {
+        let step = self.steps.get(self.position)?;
+        self.current.replay(step);
+        self.position += 1;
+        Some(step)
+    }
+
+    // Stacks as they were after the first `step` steps.
+    pub fn state_at(&self, step: usize) -> Option<Supplies> {
+        if step > self.steps.len() {
+            return None;
+        }
+
+        let mut supplies = self.initial.clone();
+        self.steps[..step].iter().for_each(|s| supplies.replay(s));
+        Some(supplies)
+    }
+
+    // The starting drawing followed by the drawing after each step.
+    pub fn trace(&self) -> String {
+        let mut supplies = self.initial.clone();
+        let mut buffer = format!("initial:\n{}\n\n", supplies);
+
+        for (i, step) in self.steps().iter().enumerate() {
+            supplies.replay(step);
+            buffer.push_str(&format!("step {}: {}\n{}\n\n", i + 1, step, supplies));
+        }
+
+        buffer
+    }
+}
@@ -0,0 +1,82 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::InstructionError;
+
+// Stack indexes are stored zero based, the text form counts from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Move { amount: usize, from: usize, to: usize },
+    OrdMove { amount: usize, from: usize, to: usize },
+    Swap { a: usize, b: usize },
+    Reverse { amount: usize, stack: usize },
+}
+
+fn number(part: &str, instruction: &str) -> Result<usize, InstructionError> {
+    part.parse::<usize>().map_err(|_| InstructionError::Malformed(instruction.to_string()))
+}
+
+// A stack is given by its name or by its number.
+fn stack(part: &str, names: &[String]) -> Result<usize, InstructionError> {
+    if let Some(idx) = names.iter().position(|name| name == part) {
+        return Ok(idx);
+    }
+
+    let idx = part.parse::<usize>().map_err(|_| InstructionError::UnknownStack(part.to_string()))?;
+    idx.checked_sub(1).ok_or(InstructionError::BadStackIndex(0))
+}
+
+impl FromStr for Instruction {
+    type Err = InstructionError;
+
+    fn from_str(instruction: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(instruction, &[])
+    }
+}
+
+impl Instruction {
+    // Stacks the instruction reads or changes.
+    pub fn stacks(&self) -> Vec<usize> {
+        match *self {
+            Instruction::Move { from, to, .. } | Instruction::OrdMove { from, to, .. } => vec![from, to],
+            Instruction::Swap { a, b } => vec![a, b],
+            Instruction::Reverse { stack, .. } => vec![stack],
+        }
+    }
+
+    pub fn parse_with(instruction: &str, names: &[String]) -> Result<Self, InstructionError> {
+        let parts: Vec<&str> = instruction.split_whitespace().collect();
+        let malformed = || InstructionError::Malformed(instruction.to_string());
+
+        match parts[..] {
+            ["move", amount, "from", from, "to", to] => Ok(Instruction::Move {
+                amount: number(amount, instruction)?,
+                from: stack(from, names)?,
+                to: stack(to, names)?,
+            }),
+            ["ordmove", amount, "from", from, "to", to] => Ok(Instruction::OrdMove {
+                amount: number(amount, instruction)?,
+                from: stack(from, names)?,
+                to: stack(to, names)?,
+            }),
+            ["swap", a, b] => Ok(Instruction::Swap { a: stack(a, names)?, b: stack(b, names)? }),
+            ["reverse", amount, "from", idx] => Ok(Instruction::Reverse {
+                amount: number(amount, instruction)?,
+                stack: stack(idx, names)?,
+            }),
+            ["move" | "ordmove" | "swap" | "reverse", ..] => Err(malformed()),
+            [verb, ..] => Err(InstructionError::UnknownVerb(verb.to_string())),
+            [] => Err(malformed()),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Move { amount, from, to } => write!(f, "move {} from {} to {}", amount, from + 1, to + 1),
+            Instruction::OrdMove { amount, from, to } => write!(f, "ordmove {} from {} to {}", amount, from + 1, to + 1),
+            Instruction::Swap { a, b } => write!(f, "swap {} {}", a + 1, b + 1),
+            Instruction::Reverse { amount, stack } => write!(f, "reverse {} from {}", amount, stack + 1),
+        }
+    }
+}
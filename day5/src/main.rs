@@ -1,12 +1,26 @@
 #![allow(dead_code)]
 
-use std::{fmt::Write, fs};
-use regex::Regex;
+use std::fs;
 use std::collections::VecDeque;
-
-type Crate = char;
-
-#[derive(Clone)]
+use crane::{Crane, Cranes, CrateMover9000, CrateMover9001, LimitedCrane};
+use error::{Error, InstructionError};
+use history::{History, Step};
+use instruction::Instruction;
+use script::{Runner, Script};
+
+mod crane;
+mod drawing;
+mod engine;
+mod error;
+mod history;
+mod instruction;
+mod planner;
+mod schedule;
+mod script;
+
+type Crate = String;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct Stack {
     crates: VecDeque<Crate>,
 }
@@ -41,42 +55,33 @@ impl Stack {
     }
 }
 
-#[derive(PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Move {
     Default,
     OrdMove,
+    Capacity(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Strict,
+    Lenient,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct Supplies {
     stacks: Vec<Stack>,
+    names: Vec<String>,
 }
 
 impl Supplies {
     pub fn new(input: &str) -> Self {
-        let last_line = input.lines().find(|line| line.starts_with(" 1")).expect("Invalid input");
-
-        let x = last_line
-            .split_whitespace()
-            .last()
-            .expect("Invalid input")
-            .parse::<usize>()
-            .expect("Invalid input");
-
-        let mut supplies = Supplies::from(x);
-
-        for line in input.lines() {
-            if !line.starts_with(" 1") {
-                supplies.find_and_push(line.trim());
-            } else {
-                break;
-            }
-        }
-
-        supplies
+        Self::parse(input).unwrap_or_else(|e| panic!("Invalid input: {}", e))
     }
 
     fn from(x: usize) -> Self {
-        Supplies { stacks: vec![Stack::new(); x] }
+        Supplies { stacks: vec![Stack::new(); x], names: (1..=x).map(|i| i.to_string()).collect() }
     }
 
     pub fn push_crate_back(&mut self, idx: usize, val: Crate) {
@@ -100,7 +105,9 @@ impl Supplies {
         let mut buffer: String = String::with_capacity(capacity);
 
         for stack in self.stacks.iter() {
-            stack.top().map(|char| buffer.write_char(*char).expect("Invalid char"));
+            if let Some(label) = stack.top() {
+                buffer.push_str(label);
+            }
         }
 
         buffer
@@ -110,71 +117,169 @@ impl Supplies {
         self.stacks[idx].append(other)
     }
 
-    pub fn find_and_push(&mut self, line: &str) {
-        const OFFSET: usize = 1;
-        const CRATE_WIDTH: usize = 3;
-
-        let mut i = 0;
-        let mut offset = 0;
-        let len = line.len() - 1;
-        let regex = Regex::new(r"(\[[A-Z]\])").unwrap();
-    
-        while len >= offset {
-            let slice: &str;
-    
-            if i == 0 {
-                slice = &line[0..offset + CRATE_WIDTH];
-            } else {
-                let end = (offset + CRATE_WIDTH).min(len + OFFSET);
-                slice = &line[offset..end];
+    // Returns the executed move, `None` if the line is not an instruction.
+    pub fn read_instruction(&mut self, instruction: &str) -> Result<Option<Step>, InstructionError> {
+        self.read_instruction_with(instruction, &CrateMover9000)
+    }
+
+    // `move` and `ordmove` are carried out by the given cranes, see `Cranes`.
+    // Lines that are not instructions are skipped, moves stop early once the source stack runs out.
+    // Blocks and asserts need the whole script, see `Supplies::run`.
+    pub fn read_instruction_with<'a>(&mut self, instruction: &str, cranes: impl Into<Cranes<'a>>) -> Result<Option<Step>, InstructionError> {
+        let instruction = script::strip_comment(instruction);
+        if instruction.trim().is_empty() {
+            return Ok(None);
+        }
+
+        match Instruction::parse_with(instruction, &self.names) {
+            Ok(parsed) => Ok(Some(self.apply(&parsed, cranes))),
+            Err(InstructionError::UnknownVerb(verb)) if matches!(verb.as_str(), "repeat" | "}" | "assert") => {
+                Err(InstructionError::ScriptOnly(instruction.trim().to_string()))
             }
-    
-            if regex.is_match(slice) {
-                let val = parse_crate(slice);
-                self.push_crate_back(i, val);
+            Err(InstructionError::UnknownVerb(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Lines following the drawing paired with their line number, counted from the start of `input`.
+    fn instruction_lines(input: &str) -> impl Iterator<Item = (usize, &str)> {
+        let lines: Vec<&str> = input.lines().collect();
+        let skip = drawing::index_line(&lines).map_or(0, |i| i + 1);
+        input.lines().enumerate().skip(skip).map(|(i, line)| (i + 1, line))
+    }
+
+    // Executes the script following the drawing.
+    pub fn run<'a>(&mut self, input: &str, cranes: impl Into<Cranes<'a>>, mode: Mode) -> error::Result<Vec<Step>> {
+        let script = Script::parse(Self::instruction_lines(input), &self.names, mode)?;
+        let mut runner = Runner { supplies: self, cranes: cranes.into(), mode, steps: Vec::new() };
+        script.run(&mut runner)?;
+        Ok(runner.steps)
+    }
+
+    // Runs the script following the drawing backwards, starting from the final stacks.
+    // Asserts are skipped as they describe the stacks going forward.
+    pub fn run_backwards<'a>(&mut self, input: &str, cranes: impl Into<Cranes<'a>>) -> error::Result<()> {
+        let script = Script::parse(Self::instruction_lines(input), &self.names, Mode::Strict)?;
+        let cranes = cranes.into();
+
+        script
+            .flatten()
+            .iter()
+            .rev()
+            .try_for_each(|(i, ins)| self.unapply(ins, cranes).map_err(|kind| Error { line: *i, kind }))
+    }
+
+    // Reverts an instruction that was fully carried out, the crates it moved must still be on top.
+    pub fn unapply<'a>(&mut self, instruction: &Instruction, cranes: impl Into<Cranes<'a>>) -> Result<(), InstructionError> {
+        let cranes = cranes.into();
+        let (stacks, taken) = match *instruction {
+            Instruction::Move { amount, from, to } | Instruction::OrdMove { amount, from, to } => {
+                (vec![from, to], Some((to, amount)))
+            }
+            Instruction::Swap { a, b } => (vec![a, b], None),
+            Instruction::Reverse { amount, stack } => (vec![stack], Some((stack, amount))),
+        };
+        self.check(&stacks, taken)?;
+
+        match *instruction {
+            Instruction::Move { amount, from, to } => {
+                cranes.moves.unlift(self, amount, from, to);
+            }
+            Instruction::OrdMove { amount, from, to } => {
+                cranes.ordmoves.unlift(self, amount, from, to);
+            }
+            Instruction::Swap { a, b } => {
+                self.swap_stacks(a, b);
+            }
+            Instruction::Reverse { amount, stack } => {
+                self.reverse_top(amount, stack);
             }
-    
-            offset += CRATE_WIDTH + OFFSET;
-            i += 1;
         }
+
+        Ok(())
     }
 
-    pub fn read_instruction(&mut self, instruction: &str) {
-        match instruction {
-            ins if ins.starts_with("move") => Self::move_ins(self, ins, Move::Default),
-            ins if ins.starts_with("ordmove") => Self::move_ins(self, ins, Move::OrdMove),
-            _ => (),
+    // Every stack has to exist and `taken.0` has to hold at least `taken.1` crates.
+    fn check(&self, stacks: &[usize], taken: Option<(usize, usize)>) -> Result<(), InstructionError> {
+        if let Some(idx) = stacks.iter().find(|idx| **idx >= self.size()) {
+            return Err(InstructionError::BadStackIndex(idx + 1));
+        }
+
+        if let Some((idx, needed)) = taken {
+            let available = self.stacks[idx].len();
+            if needed > available {
+                return Err(InstructionError::Underflow { stack: idx + 1, needed, available });
+            }
         }
+
+        Ok(())
     }
 
-    fn move_ins(supplies: &mut Self, instruction: &str, kind: Move) {
-        let parts: Vec<&str> = instruction.split_whitespace().collect();
+    pub fn apply<'a>(&mut self, instruction: &Instruction, cranes: impl Into<Cranes<'a>>) -> Step {
+        let cranes = cranes.into();
+        match *instruction {
+            Instruction::Move { amount, from, to } => self.move_crates(amount, from, to, cranes.moves),
+            Instruction::OrdMove { amount, from, to } => self.move_crates(amount, from, to, cranes.ordmoves),
+            Instruction::Swap { a, b } => self.swap_stacks(a, b),
+            Instruction::Reverse { amount, stack } => self.reverse_top(amount, stack),
+        }
+    }
+
+    // Like `apply`, but refuses instructions naming missing stacks or more crates than there are.
+    pub fn try_apply<'a>(&mut self, instruction: &Instruction, cranes: impl Into<Cranes<'a>>) -> Result<Step, InstructionError> {
+        let (stacks, taken) = match *instruction {
+            Instruction::Move { amount, from, to } | Instruction::OrdMove { amount, from, to } => {
+                (vec![from, to], Some((from, amount)))
+            }
+            Instruction::Swap { a, b } => (vec![a, b], None),
+            Instruction::Reverse { amount, stack } => (vec![stack], Some((stack, amount))),
+        };
+        self.check(&stacks, taken)?;
 
-        let amount = parts[1].parse::<usize>().expect("Invalid amount");
-        let pop_idx = parts[3].parse::<usize>().expect("Invalid stack index") - 1;
-        let push_idx = parts[5].parse::<usize>().expect("Invalid stack index") - 1;
+        Ok(self.apply(instruction, cranes))
+    }
 
-        if kind == Move::Default {
-            Self::move_default(supplies, amount, pop_idx, push_idx);
-        } else if kind == Move::OrdMove {
-            Self::move_ord(supplies, amount, pop_idx, push_idx);
+    // Moving crates onto the stack they came from leaves it as it was, whatever the crane.
+    fn move_crates(&mut self, amount: usize, pop_idx: usize, push_idx: usize, crane: &dyn Crane) -> Step {
+        let crates = if pop_idx == push_idx {
+            self.stacks[pop_idx].crates.iter().take(amount).cloned().collect()
         } else {
-            unreachable!("Invalid move type");
-        }
+            crane.lift(self, amount, pop_idx, push_idx)
+        };
+        Step::Move { from: pop_idx, to: push_idx, crates, kind: crane.kind() }
+    }
+
+    // Exchanges the whole contents of two stacks.
+    fn swap_stacks(&mut self, a: usize, b: usize) -> Step {
+        self.stacks.swap(a, b);
+        Step::Swap { a, b }
+    }
+
+    // Flips the order of the top `amount` crates of a stack.
+    fn reverse_top(&mut self, amount: usize, idx: usize) -> Step {
+        let amount = amount.min(self.stacks[idx].len());
+        let crates = self.stacks[idx].crates.make_contiguous();
+        crates[..amount].reverse();
+        Step::Reverse { stack: idx, amount }
     }
 
-    fn move_default(supplies: &mut Self, amount: usize, pop_idx: usize, push_idx: usize) {
+    fn move_default(supplies: &mut Self, amount: usize, pop_idx: usize, push_idx: usize) -> Vec<Crate> {
+        let mut moved = Vec::with_capacity(amount);
+
         for _ in 0..amount {
             let popped = supplies.pop_crate_top(pop_idx);
             if let Some(val) = popped {
-                supplies.push_crate_top(push_idx, val);
+                supplies.push_crate_top(push_idx, val.clone());
+                moved.push(val);
             } else {
                 break;
             }
         }
+
+        moved
     }
 
-    fn move_ord(supplies: &mut Self, amount: usize, pop_idx: usize, push_idx: usize) {
+    fn move_ord(supplies: &mut Self, amount: usize, pop_idx: usize, push_idx: usize) -> Vec<Crate> {
         let mut package = VecDeque::new();
 
         for _ in 0..amount {
@@ -186,35 +291,162 @@ impl Supplies {
             }
         }
 
+        let moved = package.iter().cloned().collect();
         package.append(&mut supplies.stacks[push_idx].crates);
         supplies.stacks[push_idx].crates = package;
+        moved
     }
 }
 
-fn parse_crate(slice: &str) -> Crate {
-    slice[1..].chars().next().unwrap()
+// The cranes carrying out `move` and `ordmove`, the CrateMover 9000 and 9001 like in the puzzle.
+// A model picked with `--crane` or `--capacity` carries out both.
+fn cranes_from_args(args: &[String]) -> (Box<dyn Crane>, Box<dyn Crane>) {
+    let flag = |name: &str| args.iter().position(|arg| arg == name).map(|i| args.get(i + 1).expect("Missing flag value"));
+
+    if let Some(capacity) = flag("--capacity") {
+        let capacity = capacity.parse::<usize>().ok().filter(|&capacity| capacity > 0).expect("Invalid capacity");
+        return (Box::new(LimitedCrane { capacity }), Box::new(LimitedCrane { capacity }));
+    }
+
+    match flag("--crane").map(String::as_str) {
+        None => (Box::new(CrateMover9000), Box::new(CrateMover9001)),
+        Some("9000") => (Box::new(CrateMover9000), Box::new(CrateMover9000)),
+        Some("9001") => (Box::new(CrateMover9001), Box::new(CrateMover9001)),
+        Some(model) => panic!("Unknown crane model {}", model),
+    }
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("Invalid file");
-    let mut supplies = Supplies::new(&input);
-    for line in input.lines() {
-        supplies.read_instruction(line);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (moves, ordmoves) = cranes_from_args(&args);
+    let mode = if args.iter().any(|arg| arg == "--strict") { Mode::Strict } else { Mode::Lenient };
+
+    if args.iter().any(|arg| arg == "--trace") {
+        let history = History::run(&input, moves, ordmoves, mode).unwrap_or_else(|e| {
+            println!("Error --> {e}");
+            std::process::exit(1);
+        });
+        print!("{}", history.trace());
+        println!("{}", history.current().on_top());
+        return;
+    }
+
+    let cranes = Cranes { moves: &*moves, ordmoves: &*ordmoves };
+
+    // --reverse <final drawing> <instructions>
+    if let Some(i) = args.iter().position(|arg| arg == "--reverse") {
+        let drawing = fs::read_to_string(args.get(i + 1).expect("Missing final drawing")).expect("Invalid file");
+        let instructions = fs::read_to_string(args.get(i + 2).expect("Missing instructions")).expect("Invalid file");
+        let mut supplies = Supplies::new(&drawing);
+        supplies.run_backwards(&instructions, cranes).unwrap_or_else(|e| {
+            println!("Error --> {e}");
+            std::process::exit(1);
+        });
+        println!("{}", supplies);
+        return;
+    }
+
+    // --plan <goal drawing>
+    if let Some(i) = args.iter().position(|arg| arg == "--plan") {
+        let goal = fs::read_to_string(args.get(i + 1).expect("Missing goal drawing")).expect("Invalid file");
+        let plan = planner::plan(&Supplies::new(&input), &Supplies::new(&goal), cranes.moves, 1_000_000);
+        match plan {
+            Some(instructions) => instructions.iter().for_each(|ins| println!("{}", ins)),
+            None => println!("No plan found"),
+        }
+        return;
+    }
+
+    // --schedule [--cranes K]
+    if args.iter().any(|arg| arg == "--schedule") {
+        let count = args.iter().position(|arg| arg == "--cranes")
+            .map(|i| args.get(i + 1).expect("Missing flag value").parse::<usize>().expect("Invalid crane count"));
+        let mut supplies = Supplies::new(&input);
+        let script = Script::parse(Supplies::instruction_lines(&input), &supplies.names, Mode::Strict);
+        let schedule = script.map(|script| schedule::Schedule::new(&script.flatten(), count));
+        let result = schedule.and_then(|schedule| schedule.run(&mut supplies, cranes).map(|_| schedule));
+        match result {
+            Ok(schedule) => println!("{}\n{}", schedule, supplies.on_top()),
+            Err(e) => {
+                println!("Error --> {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // --engine rope
+    if args.windows(2).any(|pair| pair[0] == "--engine" && pair[1] == "rope") {
+        let mut rope = engine::Rope::from(&Supplies::new(&input));
+        rope.run(&input, cranes).unwrap_or_else(|e| {
+            println!("Error --> {e}");
+            std::process::exit(1);
+        });
+        println!("{}", rope.on_top());
+        return;
     }
 
+    let mut supplies = Supplies::new(&input);
+    supplies.run(&input, cranes, mode).unwrap_or_else(|e| {
+        println!("Error --> {e}");
+        std::process::exit(1);
+    });
+
     println!("{}", supplies.on_top());
 }
 
 #[cfg(test)]
 mod test {
-    use crate::Supplies;
+    use crate::engine::Rope;
+    use crate::instruction::Instruction;
+    use crate::planner::plan;
+    use crate::schedule::Schedule;
+    use crate::script::Script;
+    use crate::error::DrawingError;
+    use crate::{cranes_from_args, Crane, Cranes, CrateMover9000, CrateMover9001, Error, History, InstructionError, LimitedCrane, Mode, Step, Supplies};
+
+    const EXAMPLE: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n\nmove 1 from 2 to 1\nmove 3 from 1 to 3\nmove 2 from 2 to 1\nmove 1 from 1 to 2";
+
+    fn run<'a>(input: &str, cranes: impl Into<Cranes<'a>>) -> String {
+        let mut supplies = Supplies::new(input);
+        let cranes = cranes.into();
+        for line in input.lines() {
+            supplies.read_instruction_with(line, cranes).unwrap();
+        }
+        supplies.on_top()
+    }
+
+    fn drawing(input: &str) -> String {
+        let lines: Vec<&str> = input.lines().take_while(|line| !line.trim().is_empty()).collect();
+        lines.join("\n")
+    }
+
+    #[test]
+    fn render_input() {
+        for file in ["input.txt", "input2.txt", "input3.txt"] {
+            let input = std::fs::read_to_string(file).unwrap();
+            let supplies = Supplies::new(&input);
+            assert_eq!(supplies.to_string(), drawing(&input));
+        }
+    }
+
+    #[test]
+    fn render_round_trip() {
+        let input = std::fs::read_to_string("input2.txt").unwrap();
+        let mut supplies = Supplies::new(&input);
+        for line in input.lines().filter(|line| line.starts_with("move")) {
+            supplies.read_instruction(line).unwrap();
+            assert_eq!(Supplies::new(&supplies.to_string()), supplies);
+        }
+    }
 
     #[test]
     fn move_default() {
         let input = std::fs::read_to_string("input2.txt").unwrap();
         let mut supplies = Supplies::new(&input);
         for line in input.lines() {
-            supplies.read_instruction(line);
+            supplies.read_instruction(line).unwrap();
         }
         assert_eq!(supplies.on_top(), "MHGSNWSVF".to_string());
     }
@@ -224,8 +456,464 @@ mod test {
         let input = std::fs::read_to_string("input3.txt").unwrap();
         let mut supplies = Supplies::new(&input);
         for line in input.lines() {
-            supplies.read_instruction(line);
+            supplies.read_instruction(line).unwrap();
         }
         assert_eq!(supplies.on_top(), "JHGMNWWVF".to_string());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn undo_redo() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let initial = Supplies::new(&input);
+        let mut history = History::new(initial.clone());
+        for line in input.lines() {
+            history.read_instruction(line).unwrap();
+        }
+        let steps = history.len();
+        assert_eq!(history.current().on_top(), "BQDNWJPVJ".to_string());
+
+        while history.undo().is_some() {}
+        assert_eq!(history.current(), &initial);
+        assert_eq!(history.position(), 0);
+
+        while history.redo().is_some() {}
+        assert_eq!(history.position(), steps);
+        assert_eq!(history.current().on_top(), "BQDNWJPVJ".to_string());
+    }
+
+    #[test]
+    fn state_at() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let mut history = History::new(Supplies::new(&input));
+        let mut supplies = Supplies::new(&input);
+        let mut states = vec![supplies.clone()];
+        for line in input.lines() {
+            if history.read_instruction(line).unwrap().is_some() {
+                supplies.read_instruction(line).unwrap();
+                states.push(supplies.clone());
+            }
+        }
+
+        for (step, state) in states.iter().enumerate() {
+            assert_eq!(&history.state_at(step).unwrap(), state);
+        }
+        assert!(history.state_at(states.len()).is_none());
+
+        history.undo();
+        history.undo();
+        assert_eq!(history.current(), &states[states.len() - 3]);
+        history.read_instruction("ordmove 1 from 1 to 2").unwrap();
+        assert!(history.redo().is_none());
+        assert_eq!(history.len(), states.len() - 2);
+    }
+
+    #[test]
+    fn crane_models() {
+        assert_eq!(run(EXAMPLE, &CrateMover9000), "CMZ");
+        assert_eq!(run(EXAMPLE, &CrateMover9001), "MCD");
+        assert_eq!(run(EXAMPLE, &LimitedCrane { capacity: 2 }), "MCZ");
+
+        // The input only has `ordmove`, a chosen model carries those out too.
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let chosen = |args: &[&str]| {
+            let (moves, ordmoves) = cranes_from_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+            run(&input, Cranes { moves: &*moves, ordmoves: &*ordmoves })
+        };
+        assert_eq!(run(&input, &CrateMover9000), "BQDNWJPVJ");
+        assert_eq!(chosen(&[]), "BQDNWJPVJ");
+        assert_eq!(chosen(&["--crane", "9001"]), "BQDNWJPVJ");
+        assert_ne!(chosen(&["--crane", "9000"]), "BQDNWJPVJ");
+        assert_eq!(chosen(&["--capacity", "1"]), chosen(&["--crane", "9000"]));
+        assert_eq!(chosen(&["--capacity", "100"]), chosen(&["--crane", "9001"]));
+        assert!(std::panic::catch_unwind(|| cranes_from_args(&["--capacity".to_string(), "0".to_string()])).is_err());
+    }
+
+    #[test]
+    fn self_moves_undo() {
+        let cranes: [Box<dyn Crane>; 4] = [Box::new(CrateMover9000), Box::new(CrateMover9001), Box::new(LimitedCrane { capacity: 2 }), Box::new(LimitedCrane { capacity: 1 })];
+        let initial = Supplies::new(EXAMPLE);
+
+        for crane in cranes {
+            let mut history = History::with_crane(initial.clone(), crane);
+            for line in ["move 2 from 2 to 2", "ordmove 3 from 2 to 2", "move 5 from 1 to 1", "move 1 from 3 to 3"] {
+                history.read_instruction(line).unwrap();
+                assert_eq!(history.current(), &initial);
+            }
+            assert!(matches!(&history.steps()[0], Step::Move { crates, .. } if crates == &["D", "C"]));
+
+            while history.undo().is_some() {
+                assert_eq!(history.current(), &initial);
+            }
+            while history.redo().is_some() {}
+            assert_eq!(history.current(), &initial);
+        }
+    }
+
+    #[test]
+    fn swap_and_reverse() {
+        let initial = Supplies::new(EXAMPLE);
+
+        let mut supplies = initial.clone();
+        supplies.read_instruction("swap 1 3").unwrap();
+        assert_eq!(supplies.on_top(), "PDN");
+
+        let mut supplies = initial.clone();
+        supplies.read_instruction("reverse 2 from 2").unwrap();
+        assert_eq!(supplies.on_top(), "NCP");
+        supplies.read_instruction("reverse 5 from 2").unwrap();
+        assert_eq!(supplies.on_top(), "NMP");
+
+        let mut history = History::with_crane(initial.clone(), Box::new(LimitedCrane { capacity: 2 }));
+        for line in ["swap 1 2", "reverse 3 from 1", "move 2 from 1 to 3", "swap 3 1"] {
+            history.read_instruction(line).unwrap();
+        }
+        while history.undo().is_some() {}
+        assert_eq!(history.current(), &initial);
+    }
+
+    #[test]
+    fn traced_scripts() {
+        let input = "[A] [B] [C]\n 1   2   3 \n\nrepeat 2 {\n    swap 1 2\n}\nassert top \"ABC\"";
+        let history = History::run(input, Box::new(CrateMover9000), Box::new(CrateMover9001), Mode::Lenient).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.current().on_top(), "ABC");
+        assert!(history.trace().ends_with("step 2: swap 1 2\n[A] [B] [C]\n 1   2   3 \n\n"));
+
+        let failing = input.replace("\"ABC\"", "\"BAC\"");
+        let error = History::run(&failing, Box::new(CrateMover9000), Box::new(CrateMover9001), Mode::Lenient).err().unwrap();
+        assert_eq!(error.line, 7);
+    }
+
+    #[test]
+    fn strict_errors() {
+        let error = |line: &str| {
+            let input = format!("{EXAMPLE}\n{line}");
+            Supplies::new(&input).run(&input, &CrateMover9000, Mode::Strict).unwrap_err()
+        };
+
+        assert_eq!(error("move 2 from 1 to 3"), Error {
+            line: 10,
+            kind: InstructionError::Underflow { stack: 1, needed: 2, available: 1 },
+        });
+        assert_eq!(error("move 1 from 0 to 1").kind, InstructionError::BadStackIndex(0));
+        assert_eq!(error("swap 1 4").kind, InstructionError::BadStackIndex(4));
+        assert_eq!(error("move 1 from 2").kind, InstructionError::Malformed("move 1 from 2".to_string()));
+        assert_eq!(error("move x from 2 to 1").kind, InstructionError::Malformed("move x from 2 to 1".to_string()));
+        assert_eq!(error("lift 1 from 2 to 1").kind, InstructionError::UnknownVerb("lift".to_string()));
+
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let mut supplies = Supplies::new(&input);
+        let steps = supplies.run(&input, &CrateMover9000, Mode::Strict).unwrap();
+        assert_eq!(steps.len(), input.lines().filter(|line| line.starts_with("ordmove")).count());
+        assert_eq!(supplies.on_top(), "BQDNWJPVJ");
+    }
+
+    #[test]
+    fn lenient_run() {
+        let input = format!("{EXAMPLE}\nmove 5 from 3 to 1\nlift 1 from 2 to 1");
+        let mut supplies = Supplies::new(&input);
+        let steps = supplies.run(&input, &CrateMover9000, Mode::Lenient).unwrap();
+        assert_eq!(steps.len(), 5);
+        assert_eq!(supplies.on_top(), "PM");
+    }
+
+    #[test]
+    fn run_backwards() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let initial = Supplies::new(&input);
+        let cranes: [&dyn Crane; 4] = [&CrateMover9000, &CrateMover9001, &LimitedCrane { capacity: 2 }, &LimitedCrane { capacity: 3 }];
+
+        for crane in cranes.map(Cranes::only) {
+            let mut supplies = initial.clone();
+            supplies.run(&input, crane, Mode::Strict).unwrap();
+            let last = supplies.clone();
+
+            supplies.run_backwards(&input, crane).unwrap();
+            assert_eq!(supplies, initial);
+
+            supplies.run(&input, crane, Mode::Strict).unwrap();
+            assert_eq!(supplies, last);
+        }
+
+        let program = format!("{EXAMPLE}\nswap 1 3\nreverse 3 from 1\nordmove 2 from 1 to 2");
+        let mut supplies = Supplies::new(&program);
+        supplies.run(&program, &CrateMover9000, Mode::Strict).unwrap();
+        supplies.run_backwards(&program, &CrateMover9000).unwrap();
+        assert_eq!(supplies, Supplies::new(&program));
+
+        let mut supplies = Supplies::new(EXAMPLE);
+        assert_eq!(supplies.run_backwards(EXAMPLE, &CrateMover9000).unwrap_err(), Error {
+            line: 7,
+            kind: InstructionError::Underflow { stack: 3, needed: 3, available: 1 },
+        });
+    }
+
+    #[test]
+    fn planner() {
+        let cranes: [&dyn Crane; 3] = [&CrateMover9000, &CrateMover9001, &LimitedCrane { capacity: 2 }];
+
+        for crane in cranes {
+            let start = Supplies::new(EXAMPLE);
+            let mut goal = start.clone();
+            goal.run(EXAMPLE, crane, Mode::Strict).unwrap();
+
+            let instructions = plan(&start, &goal, crane, 100_000).unwrap();
+            assert!(instructions.len() <= 4);
+
+            let mut supplies = start.clone();
+            for ins in instructions.iter() {
+                supplies.read_instruction_with(&ins.to_string(), crane).unwrap().unwrap();
+            }
+            assert_eq!(supplies, goal);
+            assert_eq!(plan(&start, &start, crane, 1), Some(vec![]));
+        }
+
+        let start = Supplies::new("[A] [B]    \n 1   2   3 ");
+        let goal = Supplies::new("[B] [A]    \n 1   2   3 ");
+        assert_eq!(plan(&start, &goal, &CrateMover9000, 100_000).unwrap().len(), 3);
+        assert_eq!(plan(&start, &Supplies::new("[B] [C]    \n 1   2   3 "), &CrateMover9000, 100_000), None);
+    }
+
+    // Swapping two full stacks is far away, the search has to give up within its state limit.
+    #[test]
+    fn planner_on_input() {
+        let start = Supplies::new(&std::fs::read_to_string("input.txt").unwrap());
+        let mut goal = start.clone();
+        goal.stacks.swap(0, 1);
+
+        assert_eq!(plan(&start, &goal, &CrateMover9000, 50_000), None);
+        let mut near = start.clone();
+        near.read_instruction("move 3 from 1 to 2").unwrap().unwrap();
+        assert_eq!(plan(&start, &near, &CrateMover9000, 50_000).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn wide_drawings() {
+        let labels = "[AB]            \n[C]  [XYZ] [D]  \n 1     2     3  ";
+        let supplies = Supplies::parse(labels).unwrap();
+        assert_eq!(supplies.on_top(), "ABXYZD");
+        assert_eq!(supplies.to_string(), "[AB]             \n [C]  [XYZ]  [D] \n  1     2     3  ");
+        assert_eq!(Supplies::parse(&supplies.to_string()).unwrap(), supplies);
+
+        let mut supplies = Supplies::parse(EXAMPLE).unwrap();
+        supplies.stacks.resize(12, supplies.stacks[2].clone());
+        supplies.names = Supplies::from(12).names;
+        let drawing = supplies.to_string();
+        assert!(drawing.ends_with(" 9  10  11  12 "));
+        assert_eq!(Supplies::parse(&drawing).unwrap(), supplies);
+        assert_eq!(supplies.on_top(), "NDPPPPPPPPPP");
+    }
+
+    #[test]
+    fn malformed_drawings() {
+        let error = |drawing: &str| Supplies::parse(drawing).unwrap_err();
+
+        assert_eq!(error("[A]\n\nmove 1 from 1 to 2"), DrawingError::MissingIndexLine);
+        assert_eq!(error("[A] [B]\n 1   3 "), DrawingError::BadIndexLine { line: 2 });
+        assert_eq!(error("[A] [B\n 1   2 "), DrawingError::Unclosed { line: 1, column: 5 });
+        assert_eq!(error("[A] []\n 1   2 "), DrawingError::BadLabel { line: 1, column: 5 });
+        assert_eq!(error("[A] B\n 1   2 "), DrawingError::StrayText { line: 1, column: 5 });
+        assert_eq!(error("  [A]\n 1   2 "), DrawingError::Misaligned { line: 1, column: 3 });
+        assert_eq!(error("[A]\n    [B]\n 1   2 "), DrawingError::Floating { line: 1, stack: 1 });
+    }
+
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    fn random_supplies(random: &mut Random, stacks: usize, crates: usize) -> Supplies {
+        let mut supplies = Supplies::from(stacks);
+        for i in 0..crates {
+            let idx = random.below(stacks);
+            supplies.push_crate_top(idx, i.to_string());
+        }
+        supplies
+    }
+
+    fn random_instruction(random: &mut Random, stacks: usize, amount: usize) -> Instruction {
+        let amount = random.below(amount) + 1;
+        let (a, b) = (random.below(stacks), random.below(stacks));
+        match random.below(10) {
+            0 => Instruction::Swap { a, b },
+            1 => Instruction::Reverse { amount, stack: a },
+            2 => Instruction::OrdMove { amount, from: a, to: b },
+            _ => Instruction::Move { amount, from: a, to: b },
+        }
+    }
+
+    #[test]
+    fn rope_matches_reference() {
+        let cranes: [&dyn Crane; 4] = [&CrateMover9000, &CrateMover9001, &LimitedCrane { capacity: 3 }, &LimitedCrane { capacity: 0 }];
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+
+        for crane in cranes {
+            for _ in 0..20 {
+                let mut supplies = random_supplies(&mut random, 5, 60);
+                let mut rope = Rope::from(&supplies);
+                assert_eq!(rope.to_supplies(), supplies);
+
+                for _ in 0..200 {
+                    let ins = random_instruction(&mut random, 5, 20);
+                    supplies.apply(&ins, crane);
+                    rope.apply(&ins, crane);
+                }
+                assert_eq!(rope.on_top(), supplies.on_top());
+                assert_eq!(rope.to_supplies(), supplies);
+            }
+        }
+
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let mut rope = Rope::from(&Supplies::new(&input));
+        rope.run(&input, &CrateMover9000).unwrap();
+        assert_eq!(rope.on_top(), "BQDNWJPVJ");
+    }
+
+    #[test]
+    fn rope_large_moves() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut rope = Rope::from(&random_supplies(&mut random, 9, 200_000));
+
+        for _ in 0..200_000 {
+            let ins = random_instruction(&mut random, 9, 100_000);
+            rope.apply(&ins, &CrateMover9000);
+        }
+
+        let total: usize = (0..rope.size()).map(|idx| rope.len(idx)).sum();
+        assert_eq!(total, 200_000);
+    }
+
+    #[test]
+    fn rope_long_moves_match_reference() {
+        let cranes: [&dyn Crane; 5] = [
+            &CrateMover9000,
+            &CrateMover9001,
+            &LimitedCrane { capacity: 1 },
+            &LimitedCrane { capacity: 7 },
+            &LimitedCrane { capacity: 1_000 },
+        ];
+        let mut random = Random(0x94d0_49bb_1331_11eb);
+
+        for crane in cranes {
+            let mut supplies = random_supplies(&mut random, 9, 5_000);
+            let mut rope = Rope::from(&supplies);
+
+            for _ in 0..2_000 {
+                let ins = random_instruction(&mut random, 9, 2_000);
+                supplies.apply(&ins, crane);
+                rope.apply(&ins, crane);
+            }
+
+            assert_eq!(rope.on_top(), supplies.on_top());
+            assert_eq!(rope.to_supplies(), supplies);
+        }
+    }
+
+    const SCRIPT: &str = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n a   b   c \n
+# Named stacks, comments and blocks.
+move 1 from b to a   # D goes on top of N
+repeat 2 {
+    move 1 from a to c
+}
+assert top \"ZCN\"
+
+repeat 3 {
+    repeat 2 {
+        swap a b
+    }
+    reverse 2 from c # twice is a no-op
+    reverse 2 from c
+}
+assert top \"ZCN\" # still the same
+move 1 from 3 to 1";
+
+    #[test]
+    fn scripts() {
+        let mut supplies = Supplies::parse(SCRIPT).unwrap();
+        assert_eq!(supplies.to_string(), SCRIPT.lines().take(4).collect::<Vec<_>>().join("\n"));
+
+        let steps = supplies.run(SCRIPT, &CrateMover9000, Mode::Strict).unwrap();
+        assert_eq!(steps.len(), 1 + 2 + 3 * (2 + 2) + 1);
+        assert_eq!(supplies.on_top(), "NCD");
+
+        supplies.run_backwards(SCRIPT, &CrateMover9000).unwrap();
+        assert_eq!(supplies, Supplies::parse(SCRIPT).unwrap());
+
+        let mut supplies = Supplies::parse(SCRIPT).unwrap();
+        assert_eq!(supplies.read_instruction("move 1 from b to a # comment").unwrap().unwrap().to_string(), "move 1 from 2 to 1");
+
+        for line in ["repeat 2 {", "}", "assert top \"NDP\""] {
+            assert_eq!(supplies.read_instruction(line), Err(InstructionError::ScriptOnly(line.to_string())));
+        }
+        assert_eq!(supplies.read_instruction("    [D]"), Ok(None));
+        assert_eq!(supplies.read_instruction("move x from a to b"), Err(InstructionError::Malformed("move x from a to b".to_string())));
+    }
+
+    #[test]
+    fn script_errors() {
+        let error = |program: &str| {
+            let input = format!("{}\n\n{}", SCRIPT.lines().take(4).collect::<Vec<_>>().join("\n"), program);
+            Supplies::parse(&input).unwrap().run(&input, &CrateMover9000, Mode::Strict).unwrap_err()
+        };
+
+        assert_eq!(error("assert top \"NDP\"\nassert top \"ND\""), Error {
+            line: 7,
+            kind: InstructionError::AssertionFailed { expected: "ND".to_string(), found: "NDP".to_string() },
+        });
+        assert_eq!(error("repeat 2 {\nswap a b"), Error { line: 6, kind: InstructionError::UnclosedBlock });
+        assert_eq!(error("swap a b\n}"), Error { line: 7, kind: InstructionError::UnexpectedBrace });
+        assert_eq!(error("repeat x {\n}").kind, InstructionError::Malformed("repeat x {".to_string()));
+        assert_eq!(error("swap a d").kind, InstructionError::UnknownStack("d".to_string()));
+        assert_eq!(error("assert top ABC").kind, InstructionError::Malformed("assert top ABC".to_string()));
+
+        assert_eq!(Supplies::parse("[A] [B]\n a   a ").unwrap_err(), DrawingError::BadIndexLine { line: 2 });
+    }
+
+    #[test]
+    fn schedule() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let initial = Supplies::new(&input);
+        let instructions = Script::parse(Supplies::instruction_lines(&input), &initial.names, Mode::Strict)
+            .unwrap()
+            .flatten();
+        let cranes: [&dyn Crane; 3] = [&CrateMover9000, &CrateMover9001, &LimitedCrane { capacity: 2 }];
+
+        for crane in cranes.map(Cranes::only) {
+            let mut sequential = initial.clone();
+            sequential.run(&input, crane, Mode::Strict).unwrap();
+
+            for limit in [None, Some(1), Some(2), Some(4)] {
+                let schedule = Schedule::new(&instructions, limit);
+                let mut concurrent = initial.clone();
+                schedule.run(&mut concurrent, crane).unwrap();
+                assert_eq!(concurrent, sequential);
+
+                assert_eq!(schedule.ticks().iter().map(Vec::len).sum::<usize>(), instructions.len());
+                for tick in schedule.ticks() {
+                    let mut stacks: Vec<usize> = tick.iter().flat_map(|(_, ins)| ins.stacks()).collect();
+                    let touched = stacks.len();
+                    stacks.sort_unstable();
+                    stacks.dedup();
+                    assert_eq!(stacks.len(), touched);
+                    assert!(limit.is_none_or(|limit| tick.len() <= limit));
+                }
+            }
+            assert_eq!(Schedule::new(&instructions, Some(1)).makespan(), instructions.len());
+            assert!(Schedule::new(&instructions, None).makespan() < instructions.len());
+        }
+
+        let program = "[A] [B] [C] [D]\n 1   2   3   4 \n\nmove 1 from 1 to 2\nmove 1 from 3 to 4\nswap 2 3\nreverse 1 from 1";
+        let supplies = Supplies::new(program);
+        let instructions = Script::parse(Supplies::instruction_lines(program), &supplies.names, Mode::Strict)
+            .unwrap()
+            .flatten();
+        let schedule = Schedule::new(&instructions, None);
+        assert_eq!(schedule.to_string(), "tick 1: move 1 from 1 to 2 | move 1 from 3 to 4\ntick 2: swap 2 3 | reverse 1 from 1\nmakespan: 2");
+    }
+}
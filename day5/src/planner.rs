@@ -0,0 +1,147 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::rc::Rc;
+
+use crate::crane::Crane;
+use crate::instruction::Instruction;
+use crate::{Crate, Stack, Supplies};
+
+// Ends a stack in an encoded layout.
+const SEPARATOR: u16 = u16::MAX;
+
+// Lower bound on the instructions left. Crates above the longest correct bottom part of
+// a stack have to be taken away and missing crates brought in, an instruction has only
+// one source and one destination so each such stack costs at least one instruction.
+fn heuristic(state: &Supplies, goal: &Supplies) -> usize {
+    let mut sources = 0;
+    let mut destinations = 0;
+
+    for (stack, target) in state.stacks.iter().zip(goal.stacks.iter()) {
+        let correct = stack.crates.iter().rev()
+            .zip(target.crates.iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if stack.len() > correct {
+            sources += 1;
+        }
+        if target.len() > correct {
+            destinations += 1;
+        }
+    }
+
+    sources.max(destinations)
+}
+
+// A reached layout with the cheapest known way of getting there. The layout is shared
+// with the key of `seen`, so every state is stored once.
+struct Node {
+    layout: Rc<[u16]>,
+    cost: usize,
+    parent: Option<(usize, Instruction)>,
+}
+
+// Layouts as crate ids, every stack top first followed by `SEPARATOR`.
+struct Codec {
+    labels: Vec<Crate>,
+    ids: HashMap<Crate, u16>,
+    names: Vec<String>,
+}
+
+impl Codec {
+    fn new(start: &Supplies) -> Self {
+        let mut labels: Vec<Crate> = start.stacks.iter().flat_map(|stack| stack.crates.iter().cloned()).collect();
+        labels.sort_unstable();
+        labels.dedup();
+        let ids = labels.iter().enumerate().map(|(id, label)| (label.clone(), id as u16)).collect();
+        Codec { labels, ids, names: start.names.clone() }
+    }
+
+    fn encode(&self, supplies: &Supplies) -> Rc<[u16]> {
+        let mut layout = Vec::with_capacity(supplies.stacks.iter().map(|stack| stack.len() + 1).sum());
+        for stack in supplies.stacks.iter() {
+            layout.extend(stack.crates.iter().map(|label| self.ids[label]));
+            layout.push(SEPARATOR);
+        }
+        layout.into()
+    }
+
+    fn decode(&self, layout: &[u16]) -> Supplies {
+        let stacks = layout.split(|&id| id == SEPARATOR).take(self.names.len())
+            .map(|ids| Stack { crates: ids.iter().map(|&id| self.labels[id as usize].clone()).collect() })
+            .collect();
+        Supplies { stacks, names: self.names.clone() }
+    }
+}
+
+fn same_crates(a: &Supplies, b: &Supplies) -> bool {
+    let sorted = |supplies: &Supplies| {
+        let mut crates: Vec<_> = supplies.stacks.iter().flat_map(|stack| stack.crates.iter().cloned()).collect();
+        crates.sort_unstable();
+        crates
+    };
+    a.size() == b.size() && sorted(a) == sorted(b)
+}
+
+// Shortest list of `move n from a to b` instructions turning `start` into `goal` under the
+// given crane, found with A*. Gives up with `None` once more than `limit` states were reached
+// or when the two layouts don't hold the same crates.
+pub fn plan(start: &Supplies, goal: &Supplies, crane: &dyn Crane, limit: usize) -> Option<Vec<Instruction>> {
+    let codec = Codec::new(start);
+    if !same_crates(start, goal) || codec.labels.len() >= SEPARATOR as usize {
+        return None;
+    }
+
+    let target = codec.encode(goal);
+    let layout = codec.encode(start);
+    let mut states = vec![Node { layout: layout.clone(), cost: 0, parent: None }];
+    let mut seen: HashMap<Rc<[u16]>, usize> = HashMap::from([(layout, 0)]);
+    let mut open = BinaryHeap::from([Reverse((heuristic(start, goal), 0, 0))]);
+
+    while let Some(Reverse((_, cost, idx))) = open.pop() {
+        if cost > states[idx].cost {
+            continue;
+        }
+
+        if states[idx].layout == target {
+            let mut instructions = Vec::new();
+            let mut at = idx;
+            while let Some((parent, ins)) = states[at].parent {
+                instructions.push(ins);
+                at = parent;
+            }
+            instructions.reverse();
+            return Some(instructions);
+        }
+
+        let state = codec.decode(&states[idx].layout);
+        for from in 0..state.size() {
+            for to in (0..state.size()).filter(|to| *to != from) {
+                for amount in 1..=state.stacks[from].len() {
+                    let ins = Instruction::Move { amount, from, to };
+                    let mut next = state.clone();
+                    next.apply(&ins, crane);
+
+                    let cost = cost + 1;
+                    let layout = codec.encode(&next);
+                    match seen.get(&layout) {
+                        Some(&known) if states[known].cost <= cost => continue,
+                        Some(&known) => {
+                            states[known].cost = cost;
+                            states[known].parent = Some((idx, ins));
+                            open.push(Reverse((cost + heuristic(&next, goal), cost, known)));
+                        }
+                        None if states.len() >= limit => return None,
+                        None => {
+                            let estimate = cost + heuristic(&next, goal);
+                            seen.insert(layout.clone(), states.len());
+                            open.push(Reverse((estimate, cost, states.len())));
+                            states.push(Node { layout, cost, parent: Some((idx, ins)) });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+use crate::crane::Cranes;
+use crate::error::{self, Error};
+use crate::instruction::Instruction;
+use crate::Supplies;
+
+// Instructions grouped into ticks, instructions of the same tick touch disjoint stacks
+// so the cranes carrying them out never get in each other's way.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Schedule {
+    ticks: Vec<Vec<(usize, Instruction)>>,
+}
+
+impl Schedule {
+    // Every instruction goes into the earliest tick after the last one touching any of its
+    // stacks, optionally with at most `cranes` instructions per tick. Instructions sharing a
+    // stack keep their order and the others commute, so the outcome is the same as running
+    // the list one by one.
+    pub fn new(instructions: &[(usize, Instruction)], cranes: Option<usize>) -> Self {
+        let mut ticks: Vec<Vec<(usize, Instruction)>> = Vec::new();
+        // Tick after the last one that touched the stack.
+        let mut ready: Vec<usize> = Vec::new();
+
+        for (line, instruction) in instructions.iter() {
+            let stacks = instruction.stacks();
+            let max = stacks.iter().max().map_or(0, |idx| idx + 1);
+            if ready.len() < max {
+                ready.resize(max, 0);
+            }
+
+            let mut tick = stacks.iter().map(|idx| ready[*idx]).max().unwrap_or(0);
+            while cranes.is_some_and(|cranes| ticks.get(tick).is_some_and(|t| t.len() >= cranes.max(1))) {
+                tick += 1;
+            }
+
+            if ticks.len() <= tick {
+                ticks.resize_with(tick + 1, Vec::new);
+            }
+            ticks[tick].push((*line, *instruction));
+            stacks.iter().for_each(|idx| ready[*idx] = tick + 1);
+        }
+
+        Schedule { ticks }
+    }
+
+    pub fn makespan(&self) -> usize {
+        self.ticks.len()
+    }
+
+    pub fn ticks(&self) -> &[Vec<(usize, Instruction)>] {
+        &self.ticks
+    }
+
+    // Runs the ticks one after another, strictly like `Supplies::try_apply`.
+    pub fn run<'a>(&self, supplies: &mut Supplies, cranes: impl Into<Cranes<'a>>) -> error::Result<()> {
+        let cranes = cranes.into();
+        for tick in self.ticks.iter() {
+            for (line, instruction) in tick.iter() {
+                supplies.try_apply(instruction, cranes).map_err(|kind| Error { line: *line, kind })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, tick) in self.ticks.iter().enumerate() {
+            let instructions: Vec<String> = tick.iter().map(|(_, ins)| ins.to_string()).collect();
+            writeln!(f, "tick {}: {}", i + 1, instructions.join(" | "))?;
+        }
+        write!(f, "makespan: {}", self.makespan())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::crane::Cranes;
+use crate::error::{self, Error, InstructionError};
+use crate::history::Step;
+use crate::instruction::Instruction;
+use crate::{Mode, Supplies};
+
+// A crane program: instructions, `repeat N { ... }` blocks and `assert top "..."` checks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Statement {
+    Instruction { line: usize, instruction: Instruction },
+    Repeat { line: usize, count: usize, body: Vec<Statement> },
+    AssertTop { line: usize, expected: String },
+}
+
+// Anything a script can be run on.
+pub trait Machine {
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), InstructionError>;
+    fn on_top(&self) -> String;
+}
+
+// Everything after `#` is a comment, unless the `#` is inside a quoted string.
+pub fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+
+    line
+}
+
+// assert top "ABC"
+fn parse_assert(line: &str) -> Option<String> {
+    let expected = line.strip_prefix("assert")?.trim_start().strip_prefix("top")?.trim();
+    let expected = expected.strip_prefix('"')?.strip_suffix('"')?;
+    (!expected.contains('"')).then(|| expected.to_string())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Script {
+    statements: Vec<Statement>,
+}
+
+impl Script {
+    // Stacks are referred to by `names` or by their number. In lenient mode unknown
+    // verbs are skipped, strict mode reports them.
+    pub fn parse<'a>(lines: impl Iterator<Item = (usize, &'a str)>, names: &[String], mode: Mode) -> error::Result<Self> {
+        // Open blocks as (line, count, body), the first one is the script itself.
+        let mut blocks = vec![(0, 1, Vec::new())];
+
+        for (i, line) in lines {
+            let line = strip_comment(line).trim();
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let malformed = || Error { line: i, kind: InstructionError::Malformed(line.to_string()) };
+
+            let statement = match parts[..] {
+                [] => continue,
+                ["}"] if blocks.len() > 1 => {
+                    let (line, count, body) = blocks.pop().unwrap();
+                    Statement::Repeat { line, count, body }
+                }
+                ["}"] => return Err(Error { line: i, kind: InstructionError::UnexpectedBrace }),
+                ["repeat", count, "{"] => {
+                    blocks.push((i, count.parse::<usize>().map_err(|_| malformed())?, Vec::new()));
+                    continue;
+                }
+                ["repeat", ..] => return Err(malformed()),
+                ["assert", ..] => Statement::AssertTop { line: i, expected: parse_assert(line).ok_or_else(malformed)? },
+                _ => match Instruction::parse_with(line, names) {
+                    Ok(instruction) => Statement::Instruction { line: i, instruction },
+                    Err(InstructionError::UnknownVerb(_)) if mode == Mode::Lenient => continue,
+                    Err(kind) => return Err(Error { line: i, kind }),
+                },
+            };
+
+            blocks.last_mut().unwrap().2.push(statement);
+        }
+
+        match blocks.pop() {
+            Some((line, _, _)) if !blocks.is_empty() => Err(Error { line, kind: InstructionError::UnclosedBlock }),
+            Some((_, _, statements)) => Ok(Script { statements }),
+            None => unreachable!("The script block is never closed"),
+        }
+    }
+
+    pub fn run(&self, machine: &mut dyn Machine) -> error::Result<()> {
+        Self::execute(&self.statements, machine)
+    }
+
+    fn execute(statements: &[Statement], machine: &mut dyn Machine) -> error::Result<()> {
+        for statement in statements {
+            match statement {
+                Statement::Instruction { line, instruction } => {
+                    machine.execute(instruction).map_err(|kind| Error { line: *line, kind })?;
+                }
+                Statement::Repeat { count, body, .. } => {
+                    for _ in 0..*count {
+                        Self::execute(body, machine)?;
+                    }
+                }
+                Statement::AssertTop { line, expected } => {
+                    let found = machine.on_top();
+                    if found != *expected {
+                        let kind = InstructionError::AssertionFailed { expected: expected.clone(), found };
+                        return Err(Error { line: *line, kind });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Instructions in the order they are executed, with repeats unrolled and asserts left out.
+    pub fn flatten(&self) -> Vec<(usize, Instruction)> {
+        fn walk(statements: &[Statement], out: &mut Vec<(usize, Instruction)>) {
+            for statement in statements {
+                match statement {
+                    Statement::Instruction { line, instruction } => out.push((*line, *instruction)),
+                    Statement::Repeat { count, body, .. } => (0..*count).for_each(|_| walk(body, out)),
+                    Statement::AssertTop { .. } => (),
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        walk(&self.statements, &mut out);
+        out
+    }
+}
+
+// Runs scripts on the reference engine, recording every executed step.
+pub struct Runner<'a> {
+    pub supplies: &'a mut Supplies,
+    pub cranes: Cranes<'a>,
+    pub mode: Mode,
+    pub steps: Vec<Step>,
+}
+
+impl Machine for Runner<'_> {
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), InstructionError> {
+        let step = match self.mode {
+            Mode::Strict => self.supplies.try_apply(instruction, self.cranes)?,
+            Mode::Lenient => self.supplies.apply(instruction, self.cranes),
+        };
+        self.steps.push(step);
+        Ok(())
+    }
+
+    fn on_top(&self) -> String {
+        self.supplies.on_top()
+    }
+}
@@ -1,30 +1,424 @@
-use std::{collections::HashSet, fs, hash::Hash};
+use std::{collections::{HashSet, VecDeque}, fs, hash::Hash, io::Read, ops::Range};
 
-fn signal_start(input: &str) -> usize {
-    let chars: Vec<char> = input.chars().collect();
-    let mut marker_chars = &chars[0..14];
+const PACKET: usize = 4;
+const MESSAGE: usize = 14;
 
-    for i in (0..chars.len()).skip(15) {
-        if are_unique(marker_chars) {
-            return i - 1
-        } else {
-            marker_chars = &chars[i-14..i];
+// Start-of-message position, `None` if the stream has no message marker.
+fn signal_start(input: &str) -> Option<usize> {
+    let mut decoder = Decoder::new();
+    let events = decoder.feed(input.as_bytes());
+
+    events.into_iter().find_map(|event| match event {
+        Event::MessageStart(offset) => Some(offset),
+        Event::PacketStart(_) => None,
+    })
+}
+
+// A marker and the payload following it, up to the next marker or the end of the stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Frame {
+    marker: Range<usize>,
+    payload: Range<usize>,
+}
+
+// Every non-overlapping marker of `window` distinct bytes, `None` if there are none.
+// Bytes before the first marker don't belong to any frame.
+fn frames(input: &[u8], window: usize) -> Option<Vec<Frame>> {
+    if window == 0 {
+        return None;
+    }
+
+    let mut markers = Vec::new();
+    let mut detector = Detector::<u8>::new(window);
+
+    for (i, byte) in input.iter().enumerate() {
+        if detector.push(*byte) {
+            markers.push(i + 1 - window..i + 1);
+            detector = Detector::new(window);
+        }
+    }
+
+    let ends = markers.iter().skip(1).map(|marker| marker.start).chain([input.len()]);
+    let frames: Vec<Frame> = markers.iter()
+        .zip(ends)
+        .map(|(marker, end)| Frame { marker: marker.clone(), payload: marker.end..end })
+        .collect();
+
+    (!frames.is_empty()).then_some(frames)
+}
+
+// Shows a marker with `radius` bytes of context, `12..16: ...abc[wxyz]def...`.
+fn diagnose(input: &[u8], frame: &Frame, radius: usize) -> String {
+    let Range { start, end } = frame.marker;
+    let before = start.saturating_sub(radius);
+    let after = (end + radius).min(input.len());
+    let text = |range: Range<usize>| String::from_utf8_lossy(&input[range]).into_owned();
+
+    format!(
+        "{}..{}: {}{}[{}]{}{}",
+        start,
+        end,
+        if before > 0 { "..." } else { "" },
+        text(before..start),
+        text(start..end),
+        text(end..after),
+        if after < input.len() { "..." } else { "" },
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Event {
+    // Bytes processed until the end of the marker.
+    PacketStart(usize),
+    MessageStart(usize),
+}
+
+// Streaming marker detection, the datastream can be fed in chunks of any size and the
+// windows carry over chunk boundaries. A detector is dropped once its marker is found.
+struct Decoder {
+    offset: usize,
+    packet: Option<Detector<u8>>,
+    message: Option<Detector<u8>>,
+}
+
+impl Decoder {
+    fn new() -> Self {
+        Decoder { offset: 0, packet: Some(Detector::new(PACKET)), message: Some(Detector::new(MESSAGE)) }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for byte in chunk.iter().copied() {
+            self.offset += 1;
+            if self.packet.as_mut().is_some_and(|packet| packet.push(byte)) {
+                self.packet = None;
+                events.push(Event::PacketStart(self.offset));
+            }
+            if self.message.as_mut().is_some_and(|message| message.push(byte)) {
+                self.message = None;
+                events.push(Event::MessageStart(self.offset));
+            }
+        }
+
+        events
+    }
+}
+
+// Characters processed until the end of the first `window` distinct characters.
+// ASCII input goes through the byte fast path.
+fn find_marker(input: &str, window: usize) -> Option<usize> {
+    if input.is_ascii() {
+        find_token_marker(input.bytes(), window)
+    } else {
+        find_token_marker(input.chars(), window)
+    }
+}
+
+// Set of the tokens currently in a marker window.
+trait TokenSet<T>: Default {
+    // `false` if the token was already in.
+    fn insert(&mut self, token: &T) -> bool;
+    fn remove(&mut self, token: &T);
+}
+
+trait Token: Sized {
+    type Set: TokenSet<Self>;
+}
+
+// One bit per possible value, 256 bits for bytes and 65536 for `u16` symbols.
+struct BitMask<const WORDS: usize>([u64; WORDS]);
+
+impl<const WORDS: usize> Default for BitMask<WORDS> {
+    fn default() -> Self {
+        BitMask([0; WORDS])
+    }
+}
+
+impl<const WORDS: usize, T: Copy + Into<usize>> TokenSet<T> for BitMask<WORDS> {
+    fn insert(&mut self, token: &T) -> bool {
+        let (word, bit) = ((*token).into() / 64, (*token).into() % 64);
+        let absent = self.0[word] & (1 << bit) == 0;
+        self.0[word] |= 1 << bit;
+        absent
+    }
+
+    fn remove(&mut self, token: &T) {
+        let (word, bit) = ((*token).into() / 64, (*token).into() % 64);
+        self.0[word] &= !(1 << bit);
+    }
+}
+
+// Anything else goes through a hash set.
+struct HashTokens<T>(HashSet<T>);
+
+impl<T> Default for HashTokens<T> {
+    fn default() -> Self {
+        HashTokens(HashSet::new())
+    }
+}
+
+impl<T: Hash + Eq + Clone> TokenSet<T> for HashTokens<T> {
+    fn insert(&mut self, token: &T) -> bool {
+        self.0.insert(token.clone())
+    }
+
+    fn remove(&mut self, token: &T) {
+        self.0.remove(token);
+    }
+}
+
+impl Token for u8 {
+    type Set = BitMask<4>;
+}
+
+impl Token for u16 {
+    type Set = BitMask<1024>;
+}
+
+impl Token for u32 {
+    type Set = HashTokens<u32>;
+}
+
+impl Token for char {
+    type Set = HashTokens<char>;
+}
+
+impl Token for String {
+    type Set = HashTokens<String>;
+}
+
+impl<'a> Token for &'a str {
+    type Set = HashTokens<&'a str>;
+}
+
+// Keeps the longest run of distinct tokens ending at the last one pushed, up to `window` long.
+// Every token enters and leaves the run once so detecting is O(n) for any token type.
+struct Detector<T: Token> {
+    window: usize,
+    set: T::Set,
+    run: VecDeque<T>,
+}
+
+impl<T: Token> Detector<T> {
+    fn new(window: usize) -> Self {
+        Detector { window, set: T::Set::default(), run: VecDeque::with_capacity(window) }
+    }
+
+    // `true` when the last `window` tokens, this one included, are all distinct.
+    fn push(&mut self, token: T) -> bool {
+        while !self.set.insert(&token) {
+            let old = self.run.pop_front().unwrap();
+            self.set.remove(&old);
+        }
+        self.run.push_back(token);
+
+        if self.run.len() > self.window {
+            let old = self.run.pop_front().unwrap();
+            self.set.remove(&old);
         }
+        self.run.len() == self.window
+    }
+}
+
+// Tokens processed until the end of the first `window` distinct tokens, for any token stream.
+fn find_token_marker<T: Token>(tokens: impl IntoIterator<Item = T>, window: usize) -> Option<usize> {
+    if window == 0 {
+        return Some(0);
     }
 
-    unreachable!("There is no solution")
+    let mut detector = Detector::new(window);
+    tokens.into_iter().position(|token| detector.push(token)).map(|i| i + 1)
 }
 
-fn are_unique<T, E>(elements: T) -> bool 
-where 
-    T: IntoIterator::<Item = E>,
-    E: Hash + Eq,
-{
-    let mut hashset = HashSet::new();
-    elements.into_iter().all(|char| hashset.insert(char))
+// Decodes stdin as it arrives.
+fn decode_stdin() {
+    let mut decoder = Decoder::new();
+    let mut buffer = [0; 4096];
+    let mut stdin = std::io::stdin().lock();
+
+    loop {
+        let read = stdin.read(&mut buffer).expect("Invalid stdin");
+        if read == 0 {
+            break;
+        }
+        for event in decoder.feed(&buffer[..read]) {
+            match event {
+                Event::PacketStart(offset) => println!("start-of-packet: {}", offset),
+                Event::MessageStart(offset) => println!("start-of-message: {}", offset),
+            }
+        }
+    }
 }
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--stdin") {
+        return decode_stdin();
+    }
+
     let input = fs::read_to_string("input.txt").unwrap();
-    println!("{}", signal_start(&input));
+
+    if std::env::args().any(|arg| arg == "--frames") {
+        for (name, window) in [("packet", PACKET), ("message", MESSAGE)] {
+            let bytes = input.trim().as_bytes();
+            match frames(bytes, window) {
+                Some(frames) => frames.iter().for_each(|frame| println!("{} {}", name, diagnose(bytes, frame, 8))),
+                None => println!("no {} markers", name),
+            }
+        }
+        return;
+    }
+
+    let input = input.trim();
+    match find_marker(input, PACKET) {
+        Some(packet) => println!("start-of-packet: {}", packet),
+        None => println!("no start-of-packet marker"),
+    }
+    match signal_start(input) {
+        Some(message) => println!("start-of-message: {}", message),
+        None => println!("no start-of-message marker"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, hash::Hash};
+
+    use crate::{diagnose, find_marker, find_token_marker, frames, signal_start, Decoder, Event, Frame, MESSAGE, PACKET};
+
+    fn are_unique<T, E>(elements: T) -> bool 
+    where 
+        T: IntoIterator::<Item = E>,
+        E: Hash + Eq,
+    {
+        let mut hashset = HashSet::new();
+        elements.into_iter().all(|char| hashset.insert(char))
+    }
+
+    // Start-of-packet and start-of-message positions.
+    fn markers(input: &str) -> (Option<usize>, Option<usize>) {
+        (find_marker(input, PACKET), find_marker(input, MESSAGE))
+    }
+
+    #[test]
+    fn examples() {
+        let examples = [
+            ("mjqjpqmgbljsphdztnvjfqwrcgsmlb", 7, 19),
+            ("bvwbjplbgvbhsrlpgdmjqwftvncz", 5, 23),
+            ("nppdvjthqldpwncqszvftbrmjlhg", 6, 23),
+            ("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg", 10, 29),
+            ("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw", 11, 26),
+        ];
+
+        for (input, packet, message) in examples {
+            assert_eq!(markers(input), (Some(packet), Some(message)));
+            assert_eq!(signal_start(input), Some(message));
+        }
+
+        assert_eq!(find_marker("aaaa", 2), None);
+        assert_eq!(find_marker("", 4), None);
+        assert_eq!(find_marker("ab", 0), Some(0));
+    }
+
+    #[test]
+    fn matches_signal_start() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        assert_eq!(find_marker(&input, 14), signal_start(&input));
+        assert_eq!(signal_start("abcabcabcabcabcabc"), None);
+
+        // Naive windows over every prefix of the input.
+        let chars: Vec<char> = input.trim().chars().collect();
+        for window in 1..=20 {
+            let naive = (window..=chars.len()).find(|end| are_unique(&chars[end - window..*end]));
+            assert_eq!(find_marker(&input, window), naive);
+        }
+    }
+
+    fn decode(chunks: &[&[u8]]) -> Vec<Event> {
+        let mut decoder = Decoder::new();
+        chunks.iter().flat_map(|chunk| decoder.feed(chunk)).collect()
+    }
+
+    #[test]
+    fn decoder_chunks() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let bytes = input.as_bytes();
+        let (packet, message) = markers(&input);
+        let expected = vec![Event::PacketStart(packet.unwrap()), Event::MessageStart(message.unwrap())];
+
+        assert_eq!(decode(&[bytes]), expected);
+        for split in 0..=bytes.len() {
+            assert_eq!(decode(&[&bytes[..split], &bytes[split..]]), expected);
+        }
+
+        let single: Vec<&[u8]> = bytes.chunks(1).collect();
+        assert_eq!(decode(&single), expected);
+        for size in 2..20 {
+            let chunks: Vec<&[u8]> = bytes.chunks(size).collect();
+            assert_eq!(decode(&chunks), expected);
+        }
+
+        assert_eq!(decode(&[b"mjqj", b"pqmgbljsphdztnvjfqwrcgsmlb"]), vec![Event::PacketStart(7), Event::MessageStart(19)]);
+        assert_eq!(decode(&[b"aaaa", b"bbbb"]), vec![]);
+    }
+
+    // Repeatedly looks for the first marker starting after the previous one.
+    fn naive_markers(input: &[u8], window: usize) -> Vec<usize> {
+        let mut starts = Vec::new();
+        let mut from = 0;
+        while let Some(start) = (from..(input.len() + 1).saturating_sub(window)).find(|s| are_unique(&input[*s..s + window])) {
+            starts.push(start);
+            from = start + window;
+        }
+        starts
+    }
+
+    #[test]
+    fn framing() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let bytes = input.trim().as_bytes();
+
+        for window in [4, 14] {
+            let frames = frames(bytes, window).unwrap();
+            let starts: Vec<usize> = frames.iter().map(|frame| frame.marker.start).collect();
+            assert_eq!(starts, naive_markers(bytes, window));
+            assert!(frames.windows(2).all(|pair| pair[0].payload.end == pair[1].marker.start));
+            assert_eq!(frames.last().unwrap().payload.end, bytes.len());
+            assert_eq!(frames[0].marker.end, find_marker(&input, window).unwrap());
+        }
+
+        let stream = b"aabcdbbefgh";
+        let frames = frames(stream, 4).unwrap();
+        assert_eq!(frames, vec![Frame { marker: 1..5, payload: 5..6 }, Frame { marker: 6..10, payload: 10..11 }]);
+        assert_eq!(diagnose(stream, &frames[0], 2), "1..5: a[abcd]bb...");
+        assert_eq!(diagnose(stream, &frames[1], 2), "6..10: ...db[befg]h");
+
+        assert_eq!(crate::frames(b"aaaaaaaa", 4), None);
+        assert_eq!(crate::frames(b"", 4), None);
+    }
+
+    #[test]
+    fn token_streams() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+
+        for window in 1..=20 {
+            let expected = find_marker(&input, window);
+            assert_eq!(find_token_marker(input.bytes(), window), expected);
+            assert_eq!(find_token_marker(input.chars(), window), expected);
+            assert_eq!(find_token_marker(input.chars().map(|c| c as u32), window), expected);
+            assert_eq!(find_token_marker(input.bytes().map(|b| b as u16 * 257), window), expected);
+        }
+
+        let symbols: Vec<u16> = input.as_bytes().chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        for window in 1..=20 {
+            let naive = (window..=symbols.len()).find(|end| are_unique(&symbols[end - window..*end]));
+            assert_eq!(find_token_marker(symbols.iter().copied(), window), naive);
+        }
+
+        let words = "ping ack ping data ack fin syn data";
+        assert_eq!(find_token_marker(words.split_whitespace(), 3), Some(4));
+        assert_eq!(find_token_marker(words.split_whitespace().map(String::from), 5), Some(7));
+        assert_eq!(find_token_marker(words.split_whitespace(), 6), None);
+        assert_eq!(find_token_marker("abc".bytes(), 0), Some(0));
+    }
 }
@@ -0,0 +1,386 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{Filesystem, NodeId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Resized,
+    Unchanged,
+}
+
+impl Change {
+    fn marker(self) -> char {
+        match self {
+            Change::Added => '+',
+            Change::Removed => '-',
+            Change::Resized => '~',
+            Change::Unchanged => ' ',
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Resized => "resized",
+            Change::Unchanged => "unchanged",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub name: String,
+    pub path: String,
+    pub change: Change,
+    pub before: usize,
+    pub after: usize,
+}
+
+// A folder present on either side. Only changed files and folders with something changed
+// below them are kept, a folder that is unchanged itself is still listed for its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirDiff {
+    pub name: String,
+    pub path: String,
+    pub change: Change,
+    pub before: usize,
+    pub after: usize,
+    pub files: Vec<FileDiff>,
+    pub folders: Vec<DirDiff>,
+}
+
+impl FileDiff {
+    pub fn delta(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+}
+
+impl DirDiff {
+    pub fn delta(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.change == Change::Unchanged && self.files.is_empty() && self.folders.is_empty()
+    }
+
+    // Every folder in the diff with its size delta, parents first.
+    pub fn deltas(&self) -> Vec<(&str, i64)> {
+        let mut deltas = vec![];
+        let mut pending = vec![self];
+
+        while let Some(diff) = pending.pop() {
+            deltas.push((diff.path.as_str(), diff.delta()));
+            pending.extend(diff.folders.iter().rev());
+        }
+
+        deltas
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        // Folders still to write, and the text closing the ones already opened.
+        let mut pending = vec![Ok(self)];
+
+        while let Some(next) = pending.pop() {
+            let diff = match next {
+                Ok(diff) => diff,
+                Err(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+            };
+
+            out.push_str(&format!(
+                "{{\"name\":{},\"path\":{},\"change\":\"{}\",\"before\":{},\"after\":{},\"delta\":{},\"files\":[",
+                json_string(&diff.name), json_string(&diff.path), diff.change.name(), diff.before, diff.after, diff.delta()
+            ));
+            for (i, file) in diff.files.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&format!(
+                    "{{\"name\":{},\"path\":{},\"change\":\"{}\",\"before\":{},\"after\":{},\"delta\":{}}}",
+                    json_string(&file.name), json_string(&file.path), file.change.name(), file.before, file.after, file.delta()
+                ));
+            }
+            out.push_str("],\"folders\":[");
+
+            pending.push(Err("]}"));
+            for (i, folder) in diff.folders.iter().enumerate().rev() {
+                pending.push(Ok(folder));
+                if i > 0 {
+                    pending.push(Err(","));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+// Dropping nested folders one at a time, the derived drop would recurse once per level.
+impl Drop for DirDiff {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.folders);
+        while let Some(mut folder) = pending.pop() {
+            pending.append(&mut folder.folders);
+        }
+    }
+}
+
+fn sizes(f: &mut fmt::Formatter<'_>, change: Change, before: usize, after: usize) -> fmt::Result {
+    match change {
+        Change::Added => write!(f, "{after}"),
+        Change::Removed => write!(f, "{before}"),
+        _ => write!(f, "{before} -> {after} ({:+})", after as i64 - before as i64),
+    }
+}
+
+// `+` added, `-` removed, `~` resized, folders first, every folder with its size delta.
+impl fmt::Display for DirDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pending = vec![(0, Ok(self))];
+
+        while let Some((depth, entry)) = pending.pop() {
+            if depth > 0 {
+                writeln!(f)?;
+            }
+
+            match entry {
+                Ok(diff) => {
+                    let slash = if diff.path == "/" { "" } else { "/" };
+                    write!(f, "{}{} {}{slash} ", "  ".repeat(depth), diff.change.marker(), diff.name)?;
+                    sizes(f, diff.change, diff.before, diff.after)?;
+
+                    pending.extend(diff.files.iter().rev().map(|file| (depth + 1, Err(file))));
+                    pending.extend(diff.folders.iter().rev().map(|folder| (depth + 1, Ok(folder))));
+                }
+                Err(file) => {
+                    write!(f, "{}{} {} ", "  ".repeat(depth), file.change.marker(), file.name)?;
+                    sizes(f, file.change, file.before, file.after)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+type Side<'a> = Option<(&'a Filesystem, NodeId)>;
+
+fn join(path: &str, name: &str) -> String {
+    if path == "/" { format!("/{name}") } else { format!("{path}/{name}") }
+}
+
+// One folder without its subfolders, along with the pairs of subfolders to compare next.
+fn diff_dir<'a>(before: Side<'a>, after: Side<'a>, name: &str, path: String) -> (DirDiff, Vec<(Side<'a>, Side<'a>, &'a str)>) {
+    let size = |side: Side| side.map_or(0, |(fs, id)| fs.size_of(id));
+    let (old, new) = (size(before), size(after));
+    let change = match (before, after) {
+        (None, _) => Change::Added,
+        (_, None) => Change::Removed,
+        _ if old != new => Change::Resized,
+        _ => Change::Unchanged,
+    };
+
+    let files = |side: Side<'a>| side.map(|(fs, id)| fs.folder(id).unwrap().files()).unwrap_or(&[]);
+    let (old_files, new_files) = (files(before), files(after));
+    let names: BTreeSet<&str> = old_files.iter().chain(new_files).map(|file| file.identifier()).collect();
+
+    let files = names.into_iter().filter_map(|name| {
+        let old = old_files.iter().find(|file| file.identifier() == name).map(|file| file.size());
+        let new = new_files.iter().find(|file| file.identifier() == name).map(|file| file.size());
+        let change = match (old, new) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            (old, new) if old != new => Change::Resized,
+            _ => return None,
+        };
+        Some(FileDiff { name: name.to_string(), path: join(&path, name), change, before: old.unwrap_or(0), after: new.unwrap_or(0) })
+    }).collect();
+
+    let folders = |side: Side<'a>| side.map_or(vec![], |(fs, id)| fs.folder(id).unwrap().folders().iter().map(|&child| fs.folder(child).unwrap().identifier()).collect());
+    let names: BTreeSet<&str> = folders(before).into_iter().chain(folders(after)).collect();
+
+    let children = names.into_iter().map(|name| {
+        let child = |side: Side<'a>| side.and_then(|(fs, id)| fs.child(id, name).map(|child| (fs, child)));
+        (child(before), child(after), name)
+    }).collect();
+
+    (DirDiff { name: name.to_string(), path, change, before: old, after: new, files, folders: vec![] }, children)
+}
+
+// Folders are compared parents first and attached to their parent children first,
+// so a deep tree costs heap rather than stack.
+fn diff_dirs<'a>(before: Side<'a>, after: Side<'a>) -> DirDiff {
+    let mut diffs: Vec<(DirDiff, usize)> = vec![];
+    let mut pending = vec![(before, after, "/", "/".to_string(), 0)];
+
+    while let Some((before, after, name, path, parent)) = pending.pop() {
+        let (diff, children) = diff_dir(before, after, name, path);
+        let index = diffs.len();
+        pending.extend(children.into_iter().rev().map(|(before, after, name)| (before, after, name, join(&diff.path, name), index)));
+        diffs.push((diff, parent));
+    }
+
+    // Siblings come off the end last first, `reverse` puts them back in name order.
+    while diffs.len() > 1 {
+        let (mut diff, parent) = diffs.pop().unwrap();
+        diff.folders.reverse();
+        if !diff.is_empty() {
+            diffs[parent].0.folders.push(diff);
+        }
+    }
+
+    let (mut root, _) = diffs.pop().unwrap();
+    root.folders.reverse();
+    root
+}
+
+impl Filesystem {
+    // What changed going from `self` to `after`, matched by name.
+    pub fn diff(&self, after: &Filesystem) -> DirDiff {
+        diff_dirs(Some((self, self.root)), Some((after, after.root)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diff::json_string;
+    use crate::fixtures::EXAMPLE;
+    use crate::{parse_input, Change, File, Filesystem};
+
+    const LATER: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir n
+$ cd a
+$ ls
+dir e
+29116 f
+3000 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+100 \"quoted\"
+$ cd /n
+$ ls
+dir m
+10 x
+$ cd m
+$ ls
+5 y";
+
+    #[test]
+    fn identical() {
+        let diff = parse_input(EXAMPLE).diff(&parse_input(EXAMPLE));
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "  / 48381165 -> 48381165 (+0)");
+        assert_eq!(diff.to_json(), "{\"name\":\"/\",\"path\":\"/\",\"change\":\"unchanged\",\"before\":48381165,\"after\":48381165,\"delta\":0,\"files\":[],\"folders\":[]}");
+    }
+
+    #[test]
+    fn tree() {
+        let diff = parse_input(EXAMPLE).diff(&parse_input(LATER));
+        let expected = "~ / 48381165 -> 23448081 (-24933084)
+  ~ a/ 94853 -> 95396 (+543)
+    ~ e/ 584 -> 684 (+100)
+      + \"quoted\" 100
+    ~ g 2557 -> 3000 (+443)
+  - d/ 24933642
+    - d.ext 5626152
+    - d.log 8033020
+    - j 4060174
+    - k 7214296
+  + n/ 15
+    + m/ 5
+      + y 5
+    + x 10";
+        assert_eq!(diff.to_string(), expected);
+
+        assert_eq!(diff.change, Change::Resized);
+        assert_eq!(diff.deltas(), [("/", -24933084), ("/a", 543), ("/a/e", 100), ("/d", -24933642), ("/n", 15), ("/n/m", 5)]);
+        assert_eq!(diff.folders[0].files[0].path, "/a/g");
+    }
+
+    #[test]
+    fn reverse() {
+        let diff = parse_input(LATER).diff(&parse_input(EXAMPLE));
+        assert_eq!(diff.delta(), 24933084);
+        assert_eq!(diff.folders.iter().map(|folder| (folder.name.as_str(), folder.change)).collect::<Vec<_>>(), [
+            ("a", Change::Resized),
+            ("d", Change::Added),
+            ("n", Change::Removed),
+        ]);
+    }
+
+    #[test]
+    fn same_size_changes_are_kept() {
+        let before = parse_input("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n5 old");
+        let after = parse_input("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n5 new");
+        let diff = before.diff(&after);
+
+        assert!(!diff.is_empty());
+        assert_eq!(diff.to_string(), "  / 5 -> 5 (+0)\n    a/ 5 -> 5 (+0)\n    + new 5\n    - old 5");
+    }
+
+    #[test]
+    fn deep_chains() {
+        const DEPTH: usize = 10_000;
+        let chain = |size| {
+            let mut fs = Filesystem::new();
+            let mut id = fs.root();
+            for _ in 0..DEPTH {
+                id = fs.add_folder_in(id, "n");
+            }
+            fs.add_file_in(id, File::new("f", size));
+            fs
+        };
+
+        let diff = chain(1).diff(&chain(2));
+        assert_eq!(diff.deltas().len(), DEPTH + 1);
+        assert!(diff.deltas().iter().all(|&(_, delta)| delta == 1));
+        assert_eq!(diff.to_string().lines().count(), DEPTH + 2);
+
+        let json = diff.to_json();
+        assert_eq!(json.matches('{').count(), DEPTH + 2);
+        assert!(json.ends_with(&"]}".repeat(DEPTH + 1)));
+    }
+
+    #[test]
+    fn json() {
+        let diff = parse_input(EXAMPLE).diff(&parse_input(LATER));
+        let json = diff.folders[0].folders[0].to_json();
+        assert_eq!(json, "{\"name\":\"e\",\"path\":\"/a/e\",\"change\":\"resized\",\"before\":584,\"after\":684,\"delta\":100,\"files\":[{\"name\":\"\\\"quoted\\\"\",\"path\":\"/a/e/\\\"quoted\\\"\",\"change\":\"added\",\"before\":0,\"after\":100,\"delta\":100}],\"folders\":[]}");
+
+        let whole = diff.to_json();
+        assert_eq!(whole.matches('{').count(), whole.matches('}').count());
+        assert!(whole.contains("\"path\":\"/d\",\"change\":\"removed\",\"before\":24933642,\"after\":0,\"delta\":-24933642"));
+
+        assert_eq!(json_string("a\\b\n\t\u{1}é"), "\"a\\\\b\\n\\t\\u0001é\"");
+    }
+}
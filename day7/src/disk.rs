@@ -0,0 +1,199 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{File, Filesystem, NodeId};
+
+// `dir/name`, as long as `name` is a single plain component and the result stays under `target`.
+fn entry_path(target: &Path, dir: &Path, name: &str) -> io::Result<PathBuf> {
+    let invalid = |reason| io::Error::new(io::ErrorKind::InvalidInput, format!("{name:?}: {reason}"));
+
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+        return Err(invalid("not a valid file name"));
+    }
+
+    let path = dir.join(name);
+    if !path.starts_with(target) || path.parent() != Some(dir) {
+        return Err(invalid("outside of the target directory"));
+    }
+    Ok(path)
+}
+
+impl Filesystem {
+    // Recreates the tree under `target`, files are sparse so only their length is real.
+    // Names that would land outside of `target` are refused before anything is written.
+    pub fn materialize(&self, target: &Path) -> io::Result<()> {
+        let mut pending = vec![(self.root, target.to_path_buf())];
+        let mut planned = vec![];
+
+        while let Some((id, path)) = pending.pop() {
+            let folder = self.folder(id).unwrap();
+            for &child in folder.folders() {
+                pending.push((child, entry_path(target, &path, self.folder(child).unwrap().identifier())?));
+            }
+            let files = folder.files().iter().map(|file| Ok((entry_path(target, &path, file.identifier())?, file.size())));
+            let files = files.collect::<io::Result<Vec<_>>>()?;
+            planned.push((path, files));
+        }
+
+        for (path, files) in planned {
+            fs::create_dir_all(&path)?;
+            for (path, size) in files {
+                fs::File::create(path)?.set_len(size as u64)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Builds a tree from a real directory, file sizes are their apparent length.
+    // Entries are read in name order and symlinks are skipped.
+    pub fn scan(source: &Path) -> io::Result<Filesystem> {
+        let mut filesystem = Filesystem::new();
+        let mut pending: Vec<(NodeId, PathBuf)> = vec![(filesystem.root, source.to_path_buf())];
+
+        while let Some((id, path)) = pending.pop() {
+            let mut entries = fs::read_dir(&path)?.collect::<io::Result<Vec<_>>>()?;
+            entries.sort_by_key(|entry| entry.file_name());
+
+            for entry in entries {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let kind = entry.file_type()?;
+
+                if kind.is_dir() {
+                    pending.push((filesystem.add_folder_in(id, &name), entry.path()));
+                } else if kind.is_file() {
+                    filesystem.add_file_in(id, File::new(&name, entry.metadata()?.len() as usize));
+                }
+            }
+        }
+
+        Ok(filesystem)
+    }
+
+    // `scan` plus a synthetic `$ cd`/`$ ls` transcript of what was found.
+    pub fn scan_with_transcript(source: &Path) -> io::Result<(Filesystem, String)> {
+        let filesystem = Filesystem::scan(source)?;
+        let transcript = filesystem.transcript();
+        Ok((filesystem, transcript))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use crate::fixtures::EXAMPLE;
+    use crate::{parse_input, part_1, Filesystem};
+
+    // A fresh directory under the system temp dir, removed on drop.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("day7-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Scratch(path)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // `du -sb` counts the directories' own sizes too, take those back out.
+    fn du(path: &Path) -> Option<usize> {
+        let output = Command::new("du").arg("-sb").arg(path).output().ok().filter(|output| output.status.success())?;
+        let total: usize = String::from_utf8(output.stdout).ok()?.split_whitespace().next()?.parse().ok()?;
+
+        let mut dirs = 0;
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            dirs += fs::metadata(&dir).unwrap().len() as usize;
+            for entry in fs::read_dir(&dir).unwrap() {
+                let entry = entry.unwrap();
+                if entry.file_type().unwrap().is_dir() {
+                    pending.push(entry.path());
+                }
+            }
+        }
+
+        Some(total - dirs)
+    }
+
+    #[test]
+    fn example_round_trip() {
+        let scratch = Scratch::new("example");
+        let fs = parse_input(EXAMPLE);
+
+        fs.materialize(&scratch.0).unwrap();
+        assert_eq!(fs::metadata(scratch.0.join("a/e/i")).unwrap().len(), 584);
+        assert!(scratch.0.join("d").is_dir());
+
+        let (scanned, transcript) = Filesystem::scan_with_transcript(&scratch.0).unwrap();
+        assert_eq!(scanned, fs);
+        assert_eq!(parse_input(&transcript), fs);
+        assert_eq!(part_1(&scanned, 100_000), 95437);
+    }
+
+    #[test]
+    fn against_du() {
+        let scratch = Scratch::new("input");
+        let fs = parse_input(&fs::read_to_string("input.txt").unwrap());
+        fs.materialize(&scratch.0).unwrap();
+
+        let scanned = Filesystem::scan(&scratch.0).unwrap();
+        assert_eq!(scanned, fs);
+
+        let Some(total) = du(&scratch.0) else { return };
+        assert_eq!(total, scanned.size());
+
+        let real = |id| du(&scratch.0.join(scanned.path_of(id).unwrap().trim_start_matches('/'))).unwrap();
+        let smallest = scanned.smallest_dir(30_000_000).unwrap();
+        assert_eq!(real(smallest), fs.size_of(fs.smallest_dir(30_000_000).unwrap()));
+
+        let within = scanned.folders_within(100_000);
+        assert!(!within.is_empty());
+        for id in within {
+            assert!(real(id) <= 100_000);
+            assert_eq!(real(id), scanned.size_of(id));
+        }
+    }
+
+    #[test]
+    fn names_stay_inside_the_target() {
+        let scratch = Scratch::new("escape");
+        let inner = scratch.0.join("out/inner");
+        let escaping = parse_input("$ cd /\n$ ls\ndir ..\n$ cd ..\n$ ls\n1 ../../escaped.txt\n5 /tmp/abs.txt");
+
+        let error = escaping.materialize(&inner).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!scratch.0.join("escaped.txt").exists());
+        assert!(!inner.exists());
+
+        for transcript in ["$ cd /\n$ ls\ndir ..", "$ cd /\n$ ls\n1 .", "$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n1 b/c"] {
+            assert!(parse_input(transcript).materialize(&inner).is_err(), "{transcript}");
+        }
+        assert!(!inner.exists());
+    }
+
+    #[test]
+    fn scan_skips_symlinks_and_sorts() {
+        let scratch = Scratch::new("links");
+        fs::create_dir_all(scratch.0.join("b/c")).unwrap();
+        fs::create_dir_all(scratch.0.join("a")).unwrap();
+        fs::write(scratch.0.join("b/c/x"), [0; 10]).unwrap();
+        fs::write(scratch.0.join("z"), [0; 3]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(scratch.0.join("b"), scratch.0.join("a/loop")).unwrap();
+
+        let scanned = Filesystem::scan(&scratch.0).unwrap();
+        assert_eq!(scanned.size(), 13);
+        assert_eq!(scanned.transcript(), "$ cd /\n$ ls\ndir a\ndir b\n3 z\n$ cd a\n$ ls\n$ cd ..\n$ cd b\n$ ls\ndir c\n$ cd c\n$ ls\n10 x\n$ cd ..\n$ cd ..\n");
+        assert!(Filesystem::scan(&scratch.0.join("missing")).is_err());
+    }
+}
@@ -1,215 +1,578 @@
-#![allow(dead_code)]
-
-#[derive(Eq, PartialEq, Debug)]
-pub struct File {
-    identifier: String,
-    size: usize,
-}
-
-impl File {
-    fn new(identifier: &str, size: usize) -> Self {
-        File { identifier: identifier.to_string(), size }
-    }
-}
-
-#[derive(Eq, PartialEq, Debug)]
-pub struct Folder {
-    identifier: String,
-    folders: Vec<Folder>,
-    files: Vec<File>,
-    outer: Option<*mut Folder>,
-}
-
-impl Folder {
-    fn new(identifier: &str, outer: Option<*mut Folder>) -> Self {
-        Folder { identifier: identifier.to_string(), folders: vec![], files: vec![], outer }
-    }
-
-    fn add_file(&mut self, file: File) -> &mut File {
-        let idx = self.files.iter_mut().position(|f| &*f.identifier == &*file.identifier).unwrap_or_else(|| {
-            self.files.push(file);
-            self.files.len() - 1
-        });
-
-        &mut self.files[idx]
-    }
-
-    fn smallest(&self, needed: usize) -> &Folder {
-        let mut min = vec![self];
-        self.folders.iter().for_each(|folder| {
-            if folder.size() >= needed { 
-                min.push(folder.smallest(needed));
-            }
-        });
-        min.sort_by_key(|folder| folder.size());
-        min.first().unwrap()
-    }
-
-    fn add_folder(&mut self, id: &str) -> &mut Folder {
-        let idx = self.folders.iter().position(|folder| &*folder.identifier == id).unwrap_or_else(|| {
-            let folder = Folder::new(id, Some(self as *mut Folder));
-            self.folders.push(folder);
-            self.folders.len() - 1
-        });
-        &mut self.folders[idx]
-    }
-
-    fn rm_folder(&mut self, id: &str) {
-        self.folders.retain(|folder| &*folder.identifier != id);
-    }
-
-    pub fn size(&self) -> usize {
-        let mut size = 0;
-        self.files.iter().for_each(|file| size += file.size);
-        self.folders.iter().for_each(|folder| size += folder.size());
-        size
-    }
-
-    fn is_within(&self, size: usize) -> bool {
-        self.size() <= size
-    }
-
-    fn add_if_within<'folder>(&'folder self, size: usize, table: &mut Vec<&'folder Folder>) {
-        self.folders.iter()
-            .for_each(|folder| {
-                if folder.is_within(size) { 
-                    table.push(folder);
-                }
-                folder.add_if_within(size, table);
-            });
-    }
-}
-
-const MAX_SIZE: usize = 70_000_000;
-
-pub struct Filesystem {
-    root: Folder,
-    current: *mut Folder,
-}
-
-impl Filesystem {
-    pub fn new() -> Self {
-        let root = Folder::new("/", None);
-        let mut fs = Filesystem { root, current: std::ptr::null_mut() };
-        fs.current = &mut fs.root;
-        fs
-    }
-
-    pub fn size(&self) -> usize {
-        self.root.size()
-    }
-
-    pub fn smallest_dir(&self, size: usize) -> Option<&Folder> {
-        let root_size = self.size();
-
-        if root_size + size <= MAX_SIZE { 
-            None 
-        } else {
-            let unused = MAX_SIZE - root_size;
-            let needed = size - unused;
-            Some(self.root.smallest(needed))
-        }
-    }
-
-    pub fn read_line(&mut self, line: &str) {
-        use Token::*;
-        let token = Token::from(line);
-        
-        match token {
-            Command(CommandKind::CD(id)) => self.cd(&*id),
-            Command(CommandKind::LS) => (),
-            Dir(id) => { self.add_folder(&*id); },
-            File(file) => { self.add_file(file); },
-        }
-    }
-
-    fn at_root(&mut self) -> bool {
-        &*self.get_current().identifier == "/"
-    }
-
-    fn get_current(&mut self) -> &mut Folder {
-        unsafe { &mut *self.current }
-    }
-
-    pub fn folders_within(&self, size: usize) -> Vec<&Folder> {
-        let mut folders = Vec::new();   
-        if self.root.is_within(size) {
-            folders.push(&self.root);
-        }
-        self.root.add_if_within(size, &mut folders);
-        folders
-    }
-
-    fn get_outer(&mut self) -> *mut Folder {
-        if self.at_root() {
-            self.current
-        } else {
-            self.get_current().outer.unwrap()
-        }
-    }
-
-    fn get_folder(&mut self, id: &str) -> Option<&mut Folder> {
-        self.get_current().folders.iter_mut().find(|folder| &*folder.identifier == id)
-    }
-
-    fn cd(&mut self, id: &str) {
-        if id == ".." {
-            self.current = self.get_current().outer.unwrap();
-        } else {
-            let new: *mut Folder = self.get_folder(id).expect("Directory doesn't exist.");
-            self.current = new;
-        }
-    }
-
-    pub fn add_folder(&mut self, id: &str) -> &mut Folder {
-        self.get_current().add_folder(id)
-    }
-
-    pub fn remove_folder(&mut self, id: &str) {
-        self.get_current().rm_folder(id);
-    }
-
-    fn add_file(&mut self, file: File) -> &mut File {
-        self.get_current().add_file(file)
-    }
-}
-
-#[derive(Debug)]
-enum CommandKind {
-    CD(String),
-    LS,
-}
-
-#[derive(Debug)]
-enum Token {
-    Command(CommandKind),
-    Dir(String),
-    File(File),
-}
-
-impl From<&str> for Token {
-    fn from(value: &str) -> Self {
-        use Token::*;
-
-        let slices: Vec<&str> = value.trim().split_whitespace().collect();
-        let size = if let Ok(size) = slices[0].parse::<usize>() { Some(size) } else { None };
-
-        match value {
-            val if val.starts_with("$ cd") => Command(CommandKind::CD(slices[2].to_string())),
-            val if val.starts_with("$ ls") => Command(CommandKind::LS),
-            val if val.starts_with("dir") => Dir(slices[1].to_string()),
-            _ if size.is_some() => File(crate::File::new(slices[1], size.unwrap())),
-            _ => unreachable!(),
-        }
-    }
-}
-
-pub fn parse_input(input: &str) -> Filesystem {
-    let mut fs = Filesystem::new();
-    input.lines().skip(1).for_each(|line| fs.read_line(line.trim()));
-    fs
-}
-
-pub fn part_1(filesystem: &Filesystem, size: usize) -> usize {
-    filesystem.folders_within(size).into_iter().fold(0, |acc, folder| acc + folder.size())
-}
\ No newline at end of file
+#![allow(dead_code)]
+
+use std::cell::Cell;
+
+mod diff;
+mod disk;
+#[cfg(test)]
+mod fixtures;
+mod lint;
+mod planner;
+mod query;
+mod shell;
+mod volume;
+
+pub use diff::{Change, DirDiff, FileDiff};
+pub use lint::{lint, parse_lenient, parse_strict, Diagnostic, Issue, Severity};
+pub use planner::{Cleanup, Plan};
+pub use query::{Hit, Query, QueryError};
+pub use shell::{Command, Shell, ShellError};
+pub use volume::{MountError, Volume};
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct File {
+    identifier: String,
+    size: usize,
+}
+
+impl File {
+    fn new(identifier: &str, size: usize) -> Self {
+        File { identifier: identifier.to_string(), size }
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+// Index of a folder in the filesystem arena. Ids are never reused, so an id
+// of a removed folder just stops resolving.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Folder {
+    identifier: String,
+    folders: Vec<NodeId>,
+    files: Vec<File>,
+    outer: Option<NodeId>,
+    // Aggregate size, `None` once something below changed until it's asked for again.
+    size: Cell<Option<usize>>,
+    // Set on mount points, the root always has one.
+    capacity: Option<usize>,
+}
+
+impl Folder {
+    fn new(identifier: &str, outer: Option<NodeId>) -> Self {
+        Folder { identifier: identifier.to_string(), folders: vec![], files: vec![], outer, size: Cell::new(Some(0)), capacity: None }
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    pub fn folders(&self) -> &[NodeId] {
+        &self.folders
+    }
+
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+
+    pub fn outer(&self) -> Option<NodeId> {
+        self.outer
+    }
+
+    // `false` if a file with that name was already there, which is kept.
+    fn add_file(&mut self, file: File) -> bool {
+        let exists = self.files.iter().any(|f| f.identifier == file.identifier);
+        if !exists {
+            self.files.push(file);
+        }
+        !exists
+    }
+
+    fn files_size(&self) -> usize {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+// Capacity of the root volume unless configured otherwise.
+const MAX_SIZE: usize = 70_000_000;
+
+pub struct Filesystem {
+    nodes: Vec<Option<Folder>>,
+    root: NodeId,
+    current: NodeId,
+    // Folder the last `ls` listed, entries read from a transcript go there.
+    listing: Option<NodeId>,
+    // A transcript `cd` failed, so where it was run from is unknown until a `cd` to an absolute path.
+    lost: bool,
+}
+
+impl Default for Filesystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Filesystem {
+    pub fn new() -> Self {
+        let mut root = Folder::new("/", None);
+        root.capacity = Some(MAX_SIZE);
+        Filesystem { nodes: vec![Some(root)], root: NodeId(0), current: NodeId(0), listing: None, lost: false }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    pub fn folder(&self, id: NodeId) -> Option<&Folder> {
+        self.nodes.get(id.0).and_then(|node| node.as_ref())
+    }
+
+    fn folder_mut(&mut self, id: NodeId) -> &mut Folder {
+        self.nodes[id.0].as_mut().expect("Invalid folder id")
+    }
+
+    fn get_current(&mut self) -> &mut Folder {
+        self.folder_mut(self.current)
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.folder(id).and_then(|folder| folder.outer)
+    }
+
+    pub fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.folder(id)?.folders.iter().copied().find(|&child| self.folder(child).unwrap().identifier == name)
+    }
+
+    // Number of live folders, root included.
+    pub fn len(&self) -> usize {
+        self.nodes.iter().filter(|node| node.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    // `id` and everything below it, parents before their children.
+    pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+        let mut order = vec![];
+        let mut stack = vec![id];
+
+        while let Some(id) = stack.pop() {
+            if let Some(folder) = self.folder(id) {
+                order.push(id);
+                stack.extend(folder.folders.iter().rev());
+            }
+        }
+
+        order
+    }
+
+    // Every folder below `id` with its size.
+    fn sizes(&self, id: NodeId) -> Vec<(NodeId, usize)> {
+        self.descendants(id).into_iter().map(|id| (id, self.size_of(id))).collect()
+    }
+
+    // Marks `id` and its ancestors stale, stopping at the first one that already is.
+    fn invalidate(&self, mut id: NodeId) {
+        while let Some(folder) = self.folder(id) {
+            if folder.size.take().is_none() {
+                return;
+            }
+            match folder.outer {
+                Some(outer) => id = outer,
+                None => return,
+            }
+        }
+    }
+
+    // Cached size of `id`, only the stale folders below it are recomputed.
+    pub fn size_of(&self, id: NodeId) -> usize {
+        let Some(folder) = self.folder(id) else { return 0 };
+        if let Some(size) = folder.size.get() {
+            return size;
+        }
+
+        let mut pending = vec![(id, false)];
+        while let Some((id, expanded)) = pending.pop() {
+            let folder = self.folder(id).unwrap();
+            if expanded {
+                let below: usize = folder.folders.iter().map(|child| self.folder(*child).unwrap().size.get().unwrap()).sum();
+                folder.size.set(Some(folder.files_size() + below));
+            } else {
+                pending.push((id, true));
+                pending.extend(folder.folders.iter().filter(|child| self.folder(**child).unwrap().size.get().is_none()).map(|&child| (child, false)));
+            }
+        }
+
+        folder.size.get().unwrap()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size_of(self.root)
+    }
+
+    // Smallest folder on the root volume whose deletion leaves room for `size`.
+    pub fn smallest_dir(&self, size: usize) -> Option<NodeId> {
+        self.smallest_dir_in(self.root, size)
+    }
+
+    // Replays one transcript line. Failed commands are skipped like a shell would,
+    // the transcript carries their error message as output.
+    pub fn read_line(&mut self, line: &str) {
+        use Token::*;
+        let token = Token::from(line);
+
+        match token {
+            Command(command) => { let _ = self.replay(&command); },
+            Dir(id) => if let Some(listing) = self.listing { self.add_folder_in(listing, &id); },
+            File(file) => if let Some(listing) = self.listing { self.add_file_in(listing, file); },
+            Output(_) => (),
+        }
+    }
+
+    // Runs a transcript command. Once a `cd` fails, relative `cd`s are skipped and listings
+    // are not attached anywhere, their entries belong to a directory the tree doesn't have.
+    pub(crate) fn replay(&mut self, line: &str) -> Result<String, ShellError> {
+        match line.parse::<Command>() {
+            Ok(Command::Cd(path)) if self.lost && !path.starts_with('/') => {
+                self.listing = None;
+                Ok(String::new())
+            }
+            Ok(Command::Cd(_)) => {
+                let result = self.execute(line);
+                self.lost = result.is_err();
+                result
+            }
+            _ => {
+                let result = self.execute(line);
+                if self.lost {
+                    self.listing = None;
+                }
+                result
+            }
+        }
+    }
+
+    fn at_root(&self) -> bool {
+        self.current == self.root
+    }
+
+    pub fn folders_within(&self, size: usize) -> Vec<NodeId> {
+        self.sizes(self.root).into_iter().filter(|&(_, s)| s <= size).map(|(id, _)| id).collect()
+    }
+
+    // Moves to `path` like the shell's `cd`, the current folder stays where it was on errors.
+    pub fn cd(&mut self, path: &str) -> Result<NodeId, ShellError> {
+        self.current = self.resolve_dir(Some(path))?;
+        Ok(self.current)
+    }
+
+    pub fn add_folder(&mut self, id: &str) -> NodeId {
+        self.add_folder_in(self.current, id)
+    }
+
+    fn add_folder_in(&mut self, parent: NodeId, id: &str) -> NodeId {
+        if let Some(existing) = self.child(parent, id) {
+            return existing;
+        }
+
+        let new = NodeId(self.nodes.len());
+        self.nodes.push(Some(Folder::new(id, Some(parent))));
+        self.folder_mut(parent).folders.push(new);
+        new
+    }
+
+    pub fn remove_folder(&mut self, id: &str) {
+        if let Some(target) = self.child(self.current, id) {
+            self.remove_node(target);
+        }
+    }
+
+    // Drops `target` and its subtree, a current folder inside it moves up to `target`'s parent.
+    fn remove_node(&mut self, target: NodeId) {
+        let Some(outer) = self.parent(target) else { return };
+
+        for removed in self.descendants(target) {
+            if removed == self.current {
+                self.current = outer;
+            }
+            self.nodes[removed.0] = None;
+        }
+        self.folder_mut(outer).folders.retain(|&child| child != target);
+        self.invalidate(outer);
+    }
+
+    fn add_file(&mut self, file: File) {
+        self.add_file_in(self.current, file);
+    }
+
+    fn add_file_in(&mut self, dir: NodeId, file: File) {
+        if self.folder_mut(dir).add_file(file) {
+            self.invalidate(dir);
+        }
+    }
+
+    // `false` if there was no such file.
+    fn remove_file_in(&mut self, dir: NodeId, name: &str) -> bool {
+        let folder = self.folder_mut(dir);
+        let before = folder.files.len();
+        folder.files.retain(|file| file.identifier != name);
+
+        let removed = folder.files.len() < before;
+        if removed {
+            self.invalidate(dir);
+        }
+        removed
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Command(String),
+    Dir(String),
+    File(File),
+    Output(String),
+}
+
+impl From<&str> for Token {
+    fn from(value: &str) -> Self {
+        use Token::*;
+
+        let slices: Vec<&str> = value.split_whitespace().collect();
+
+        match value.strip_prefix("$ ") {
+            Some(command) => Command(command.to_string()),
+            None => match slices[..] {
+                ["dir", id] => Dir(id.to_string()),
+                [size, id] if size.parse::<usize>().is_ok() => File(crate::File::new(id, size.parse().unwrap())),
+                _ => Output(value.to_string()),
+            },
+        }
+    }
+}
+
+pub fn parse_input(input: &str) -> Filesystem {
+    let mut fs = Filesystem::new();
+    input.lines().for_each(|line| fs.read_line(line.trim()));
+    fs.listing = None;
+    fs
+}
+
+pub fn part_1(filesystem: &Filesystem, size: usize) -> usize {
+    filesystem.folders_within(size).into_iter().map(|folder| filesystem.size_of(folder)).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::{EXAMPLE, Random};
+    use crate::{parse_input, part_1, File, Filesystem, NodeId, ShellError};
+
+    #[test]
+    fn example() {
+        let fs = parse_input(EXAMPLE);
+        assert_eq!(fs.size(), 48381165);
+        assert_eq!(part_1(&fs, 100_000), 95437);
+
+        let smallest = fs.smallest_dir(30_000_000).unwrap();
+        assert_eq!(fs.path_of(smallest).unwrap(), "/d");
+        assert_eq!(fs.size_of(smallest), 24933642);
+    }
+
+    #[test]
+    fn input() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let fs = parse_input(&input);
+        let smallest = fs.smallest_dir(30_000_000).unwrap();
+        assert!(fs.size_of(smallest) >= 30_000_000 - (70_000_000 - fs.size()));
+        assert!(part_1(&fs, 100_000) > 0);
+    }
+
+    // Pushing a sibling used to reallocate the parent's Vec under live parent pointers.
+    #[test]
+    fn siblings_then_back_up() {
+        let mut fs = Filesystem::new();
+
+        for i in 0..1000 {
+            fs.add_folder(&format!("d{i}"));
+        }
+        for i in 0..1000 {
+            fs.cd(&format!("d{i}")).unwrap();
+            fs.read_line("$ ls");
+            for j in 0..20 {
+                fs.add_folder(&format!("s{j}"));
+                fs.read_line(&format!("{} f{j}", i + j));
+            }
+            fs.cd("s19").unwrap();
+            assert_eq!(fs.path_of(fs.current()).unwrap(), format!("/d{i}/s19"));
+            fs.cd("..").unwrap();
+            fs.cd("..").unwrap();
+            assert_eq!(fs.current(), fs.root());
+        }
+
+        assert_eq!(fs.len(), 1 + 1000 + 1000 * 20);
+        assert_eq!(fs.size(), (0..1000).flat_map(|i| (0..20).map(move |j| i + j)).sum::<usize>());
+        assert_eq!(fs.folder(fs.root()).unwrap().folders().len(), 1000);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let mut fs = Filesystem::new();
+        let depth = 50_000;
+
+        for i in 0..depth {
+            fs.add_folder("n");
+            fs.read_line("$ ls");
+            fs.read_line(&format!("1 f{i}"));
+            fs.cd("n").unwrap();
+        }
+        assert_eq!(fs.size(), depth);
+        assert_eq!(fs.size_of(fs.current()), 0);
+
+        for _ in 0..depth {
+            fs.cd("..").unwrap();
+        }
+        assert_eq!(fs.current(), fs.root());
+
+        fs.cd("..").unwrap();
+        assert_eq!(fs.current(), fs.root());
+
+        let within = fs.folders_within(10);
+        assert_eq!(within.len(), 11);
+        assert_eq!(within.iter().map(|&id| fs.size_of(id)).sum::<usize>(), 55);
+
+        let transcript = fs.transcript();
+        assert_eq!(transcript.lines().count(), 5 * depth + 2);
+        assert_eq!(parse_input(&transcript), fs);
+    }
+
+    #[test]
+    fn duplicates_are_merged() {
+        let mut fs = Filesystem::new();
+        let a = fs.add_folder("a");
+        assert_eq!(fs.add_folder("a"), a);
+
+        fs.cd("a").unwrap();
+        fs.read_line("$ ls");
+        fs.read_line("10 x");
+        fs.read_line("10 x");
+        fs.cd("..").unwrap();
+        fs.read_line("$ ls");
+        fs.read_line("dir a");
+
+        assert_eq!(fs.len(), 2);
+        assert_eq!(fs.size(), 10);
+    }
+
+    #[test]
+    fn removed_ids_stop_resolving() {
+        let mut fs = Filesystem::new();
+        let a = fs.add_folder("a");
+        fs.cd("a").unwrap();
+        let b = fs.add_folder("b");
+        fs.cd("b").unwrap();
+        fs.read_line("$ ls");
+        fs.read_line("5 y");
+        fs.cd("..").unwrap();
+        fs.cd("..").unwrap();
+        let c = fs.add_folder("c");
+
+        fs.remove_folder("a");
+        assert_eq!(fs.folder(a), None);
+        assert_eq!(fs.folder(b), None);
+        assert_eq!(fs.parent(b), None);
+        assert_eq!(fs.size_of(a), 0);
+        assert_eq!(fs.size(), 0);
+        assert_eq!(fs.folder(fs.root()).unwrap().folders(), &[c]);
+
+        let again = fs.add_folder("a");
+        assert_ne!(again, a);
+        fs.cd("a").unwrap();
+        assert_eq!(fs.current(), again);
+        assert_eq!(fs.len(), 3);
+    }
+
+    #[test]
+    fn cd_into_missing() {
+        let mut fs = parse_input(EXAMPLE);
+        let a = fs.cd("/a").unwrap();
+
+        assert_eq!(fs.cd("nope"), Err(ShellError::NotFound("nope".to_string())));
+        assert_eq!(fs.cd("f"), Err(ShellError::NotADirectory("f".to_string())));
+        assert_eq!(fs.current(), a);
+        assert_eq!(fs.cd("e/../.."), Ok(fs.root()));
+    }
+
+    // Sum of every file below `id`, without touching the cache.
+    fn naive_size(fs: &Filesystem, id: NodeId) -> usize {
+        fs.descendants(id).into_iter().map(|id| fs.folder(id).unwrap().files_size()).sum()
+    }
+
+    #[test]
+    fn cached_sizes_on_random_trees() {
+        let mut random = Random(0x2545F4914F6CDD1D);
+        let mut fs = Filesystem::new();
+        let mut ids = vec![fs.root()];
+
+        while ids.len() < 100_000 {
+            let pick = ids[random.next(ids.len())];
+            if fs.folder(pick).is_none() {
+                continue;
+            }
+
+            match random.next(100) {
+                0..=59 => ids.push(fs.add_folder_in(pick, &format!("d{}", ids.len()))),
+                60..=89 => fs.add_file_in(pick, File::new(&format!("f{}", random.next(8)), random.next(100_000))),
+                90..=94 if pick != fs.root() => fs.remove_node(pick),
+                _ => { fs.remove_file_in(pick, &format!("f{}", random.next(8))); },
+            }
+
+            if ids.len() % 997 == 0 {
+                let probe = ids[random.next(ids.len())];
+                if fs.folder(probe).is_some() {
+                    assert_eq!(fs.size_of(probe), naive_size(&fs, probe));
+                }
+                assert_eq!(fs.size(), naive_size(&fs, fs.root()));
+            }
+        }
+
+        for &id in ids.iter().filter(|&&id| fs.folder(id).is_some()) {
+            assert_eq!(fs.size_of(id), naive_size(&fs, id));
+        }
+
+        let naive_within: usize = fs.descendants(fs.root()).into_iter().map(|id| naive_size(&fs, id)).filter(|&size| size <= 100_000).sum();
+        assert_eq!(part_1(&fs, 100_000), naive_within);
+
+        let needed = fs.size() / 3;
+        let smallest = fs.smallest_dir(70_000_000_usize.saturating_sub(fs.size()) + needed).unwrap();
+        let naive_smallest = fs.descendants(fs.root()).into_iter().map(|id| naive_size(&fs, id)).filter(|&size| size >= needed).min();
+        assert_eq!(Some(fs.size_of(smallest)), naive_smallest);
+    }
+
+    #[test]
+    fn cached_sizes_on_a_deep_chain() {
+        let mut random = Random(0x9E3779B97F4A7C15);
+        let mut fs = Filesystem::new();
+        let mut chain = vec![fs.root()];
+
+        for i in 0..100_000 {
+            let next = fs.add_folder_in(*chain.last().unwrap(), "n");
+            fs.add_file_in(next, File::new("f", i % 7));
+            chain.push(next);
+        }
+        assert_eq!(fs.size(), naive_size(&fs, fs.root()));
+
+        for i in 0..50 {
+            let at = chain[random.next(chain.len())];
+            fs.add_file_in(at, File::new(&format!("g{i}"), 1000));
+            assert_eq!(fs.size(), naive_size(&fs, fs.root()));
+            assert_eq!(fs.size_of(at), naive_size(&fs, at));
+        }
+
+        fs.remove_node(chain[50_000]);
+        assert_eq!(fs.size(), naive_size(&fs, fs.root()));
+        assert_eq!(fs.size_of(chain[49_999]), naive_size(&fs, chain[49_999]));
+        assert_eq!(fs.size_of(chain[60_000]), 0);
+    }
+}
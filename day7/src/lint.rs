@@ -0,0 +1,355 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::{Command, File, Filesystem, NodeId, ShellError, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    MissingRoot,
+    UnknownDirectory(String),
+    CommandFailed(ShellError),
+    UnknownLine(String),
+    OrphanEntry(String),
+    // Listed after a failed `cd`, the directory it belongs to is unknown.
+    UnplacedEntry(String),
+    DuplicateEntry(String),
+    SizeConflict { path: String, before: usize, after: usize },
+    KindConflict(String),
+    // What lenient mode did instead of failing.
+    ImpliedDirectory(String),
+    AdoptedEntry { entry: String, dir: String },
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        use Issue::*;
+
+        match self {
+            UnknownDirectory(_) | CommandFailed(_) | UnknownLine(_) | OrphanEntry(_) => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Issue::*;
+
+        match self {
+            MissingRoot => write!(f, "transcript doesn't start with `$ cd /`"),
+            UnknownDirectory(path) => write!(f, "cd into unknown directory `{path}`"),
+            CommandFailed(error) => write!(f, "command failed: {error}"),
+            UnknownLine(text) => write!(f, "unrecognised line `{text}`"),
+            OrphanEntry(text) => write!(f, "`{text}` outside of an `ls` listing"),
+            UnplacedEntry(text) => write!(f, "`{text}` listed in an unknown directory, skipped"),
+            DuplicateEntry(path) => write!(f, "`{path}` listed twice in the same listing"),
+            SizeConflict { path, before, after } => write!(f, "`{path}` listed with {after} bytes, was {before}"),
+            KindConflict(path) => write!(f, "`{path}` listed as both a file and a directory"),
+            ImpliedDirectory(path) => write!(f, "created implied directory `{path}`"),
+            AdoptedEntry { entry, dir } => write!(f, "`{entry}` outside of an `ls` listing, added to `{dir}`"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub issue: Issue,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.issue.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity() {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "line {}: {severity}: {}", self.line, self.issue)
+    }
+}
+
+// Replays a transcript like `parse_input` while keeping track of what looks wrong.
+// Strict mode skips anything it can't apply, lenient mode repairs what it can.
+struct Linter {
+    fs: Filesystem,
+    lenient: bool,
+    diagnostics: Vec<Diagnostic>,
+    line: usize,
+    // Whether the last command prints something, the lines after it are its output.
+    expects_output: bool,
+    listed: HashSet<String>,
+}
+
+impl Linter {
+    fn report(&mut self, issue: Issue) {
+        self.diagnostics.push(Diagnostic { line: self.line, issue });
+    }
+
+    fn read_line(&mut self, text: &str) {
+        match Token::from(text) {
+            Token::Command(command) => self.command(&command),
+            Token::Dir(name) => self.entry(text, &name, None),
+            Token::File(file) => self.entry(text, &file.identifier.clone(), Some(file)),
+            Token::Output(_) if self.expects_output && self.fs.listing.is_none() => (),
+            Token::Output(_) => self.report(Issue::UnknownLine(text.to_string())),
+        }
+    }
+
+    fn command(&mut self, line: &str) {
+        self.listed.clear();
+        self.fs.listing = None;
+
+        let result = match line.parse::<Command>() {
+            Ok(Command::Cd(path)) if self.lenient && self.fs.resolve(self.fs.current, &path).is_none() => {
+                self.recover_cd(&path);
+                return;
+            }
+            Ok(command) => self.fs.replay(line).map(|_| command),
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(command) => self.expects_output = !matches!(command, Command::Cd(_) | Command::Mkdir { .. } | Command::Rm { .. }),
+            Err(ShellError::NotFound(path)) if line.split_whitespace().next() == Some("cd") => {
+                self.expects_output = true;
+                self.report(Issue::UnknownDirectory(path));
+            }
+            Err(error) => {
+                self.expects_output = true;
+                self.report(Issue::CommandFailed(error));
+            }
+        }
+    }
+
+    fn recover_cd(&mut self, path: &str) {
+        self.fs.listing = None;
+        self.expects_output = false;
+
+        match self.fs.mkdir(path, true) {
+            Ok(id) => {
+                self.report(Issue::ImpliedDirectory(self.fs.path_of(id).unwrap()));
+                self.fs.current = id;
+                self.fs.lost = false;
+            }
+            Err(error) => {
+                self.expects_output = true;
+                self.report(Issue::CommandFailed(error));
+            }
+        }
+    }
+
+    fn entry(&mut self, text: &str, name: &str, file: Option<File>) {
+        let dir = match self.fs.listing {
+            Some(dir) => dir,
+            None if self.fs.lost => return self.report(Issue::UnplacedEntry(text.to_string())),
+            None if self.expects_output => return,
+            None if self.lenient => {
+                let dir = self.fs.current;
+                self.report(Issue::AdoptedEntry { entry: text.to_string(), dir: self.fs.path_of(dir).unwrap() });
+                dir
+            }
+            None => return self.report(Issue::OrphanEntry(text.to_string())),
+        };
+
+        let path = self.fs.file_path(dir, name);
+        if !self.listed.insert(name.to_string()) {
+            self.report(Issue::DuplicateEntry(path.clone()));
+        }
+
+        let existing = self.fs.folder(dir).unwrap().files().iter().find(|f| f.identifier == name).map(|f| f.size);
+        match file {
+            None => {
+                if existing.is_some() {
+                    self.report(Issue::KindConflict(path));
+                }
+                self.fs.add_folder_in(dir, name);
+            }
+            Some(file) => {
+                if self.fs.child(dir, name).is_some() {
+                    self.report(Issue::KindConflict(path.clone()));
+                }
+                match existing {
+                    Some(before) if before != file.size => {
+                        self.report(Issue::SizeConflict { path, before, after: file.size });
+                        if self.lenient {
+                            self.resize(dir, file);
+                        }
+                    }
+                    _ => self.fs.add_file_in(dir, file),
+                }
+            }
+        }
+    }
+
+    // Later listings win in lenient mode.
+    fn resize(&mut self, dir: NodeId, file: File) {
+        let folder = self.fs.folder_mut(dir);
+        folder.files.iter_mut().find(|f| f.identifier == file.identifier).unwrap().size = file.size;
+        self.fs.invalidate(dir);
+    }
+}
+
+fn run(input: &str, lenient: bool) -> (Filesystem, Vec<Diagnostic>) {
+    let mut linter = Linter { fs: Filesystem::new(), lenient, diagnostics: vec![], line: 0, expects_output: false, listed: HashSet::new() };
+    let mut started = false;
+
+    for (i, text) in input.lines().enumerate() {
+        let text = text.trim();
+        linter.line = i + 1;
+        if text.is_empty() {
+            continue;
+        }
+
+        if !started && text != "$ cd /" {
+            linter.report(Issue::MissingRoot);
+        }
+        started = true;
+        linter.read_line(text);
+    }
+
+    linter.fs.listing = None;
+    (linter.fs, linter.diagnostics)
+}
+
+// Everything suspicious in a transcript, in line order.
+pub fn lint(input: &str) -> Vec<Diagnostic> {
+    run(input, false).1
+}
+
+// Parses a transcript like `parse_input` along with what it had to skip.
+pub fn parse_strict(input: &str) -> (Filesystem, Vec<Diagnostic>) {
+    run(input, false)
+}
+
+// Parses a transcript repairing what it can: unknown directories are created on `cd`,
+// entries outside a listing go to the current directory and later sizes win.
+pub fn parse_lenient(input: &str) -> (Filesystem, Vec<Diagnostic>) {
+    run(input, true)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::EXAMPLE;
+    use crate::lint::{lint, parse_lenient, parse_strict, run, Issue, Severity};
+    use crate::{parse_input, Shell, ShellError};
+
+    const MESSY: &str = "$ ls
+dir a
+10 x
+$ cd b
+$ ls
+5 y
+$ cd /a
+20 stray
+$ ls
+7 z
+7 z
+8 z
+dir z
+$ frobnicate
+oops
+$ cd /
+$ ls
+12 x
+garbage here
+$ du -s
+10 /";
+
+    fn render(diagnostics: &[crate::lint::Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect()
+    }
+
+    #[test]
+    fn clean_transcripts() {
+        assert_eq!(lint(EXAMPLE), vec![]);
+        assert_eq!(lint(&std::fs::read_to_string("input.txt").unwrap()), vec![]);
+
+        let mut shell = Shell::new(parse_input(EXAMPLE));
+        for line in ["cd /", "ls", "cd a", "pwd", "tree", "du", "mkdir -p x/y", "cd x/y", "ls", "cd /"] {
+            shell.run(line).unwrap();
+        }
+        assert_eq!(lint(shell.transcript()), vec![]);
+    }
+
+    #[test]
+    fn strict() {
+        let diagnostics = lint(MESSY);
+        assert_eq!(render(&diagnostics), [
+            "line 1: warning: transcript doesn't start with `$ cd /`",
+            "line 4: error: cd into unknown directory `b`",
+            "line 6: warning: `5 y` listed in an unknown directory, skipped",
+            "line 8: error: `20 stray` outside of an `ls` listing",
+            "line 11: warning: `/a/z` listed twice in the same listing",
+            "line 12: warning: `/a/z` listed twice in the same listing",
+            "line 12: warning: `/a/z` listed with 8 bytes, was 7",
+            "line 13: warning: `/a/z` listed twice in the same listing",
+            "line 13: warning: `/a/z` listed as both a file and a directory",
+            "line 14: error: command failed: frobnicate: command not found",
+            "line 18: warning: `/x` listed with 12 bytes, was 10",
+            "line 19: error: unrecognised line `garbage here`",
+        ]);
+        assert_eq!(diagnostics.iter().filter(|d| d.severity() == Severity::Error).count(), 4);
+
+        // Strict mode ends up with the same tree as `parse_input`, `5 y` was listed in `/b`.
+        let expected = parse_input("$ cd /\n$ ls\ndir a\n10 x\n$ cd a\n$ ls\n7 z\ndir z");
+        assert_eq!(parse_input(MESSY), expected);
+        assert_eq!(run(MESSY, false).0, expected);
+    }
+
+    #[test]
+    fn lenient() {
+        let (fs, diagnostics) = parse_lenient(MESSY);
+        let issues: Vec<&Issue> = diagnostics.iter().map(|diagnostic| &diagnostic.issue).collect();
+
+        assert!(issues.contains(&&Issue::ImpliedDirectory("/b".to_string())));
+        assert!(issues.contains(&&Issue::AdoptedEntry { entry: "20 stray".to_string(), dir: "/a".to_string() }));
+        assert!(!issues.iter().any(|issue| matches!(issue, Issue::UnknownDirectory(_) | Issue::OrphanEntry(_))));
+        assert_eq!(render(&diagnostics)[1], "line 4: warning: created implied directory `/b`");
+
+        assert_eq!(fs.query(&"path /b/y".parse().unwrap())[0].size, 5);
+        assert_eq!(fs.query(&"path /a/stray".parse().unwrap())[0].size, 20);
+        let a = fs.resolve(fs.root(), "/a").unwrap();
+        assert_eq!(fs.folder(a).unwrap().files().iter().find(|file| file.identifier() == "z").unwrap().size(), 8);
+        assert!(fs.child(a, "z").is_some());
+        assert_eq!(fs.size_of(a), 28);
+        assert_eq!(fs.size(), 12 + 5 + 28);
+    }
+
+    #[test]
+    fn implied_paths() {
+        let (fs, diagnostics) = parse_lenient("$ cd /\n$ cd x/y/z\n$ ls\n1 f\n$ cd ../..\n$ ls\n2 g\n$ cd /x/y/z/f");
+        assert_eq!(render(&diagnostics), [
+            "line 2: warning: created implied directory `/x/y/z`",
+            "line 8: error: command failed: /x/y/z/f: Not a directory",
+        ]);
+        assert_eq!(fs.size(), 3);
+        assert_eq!(fs.path_of(fs.current()).unwrap(), "/x");
+
+        assert_eq!(lint("$ cd /\n$ cd x/y/z\n$ ls\n1 f")[0].issue, Issue::UnknownDirectory("x/y/z".to_string()));
+        assert_eq!(lint("$ cd /\n$ cd")[0].issue, Issue::CommandFailed(ShellError::Usage("cd <path>")));
+    }
+
+    #[test]
+    fn lost_after_failed_cd() {
+        let input = "$ cd /\n$ ls\ndir a\n$ cd b\n$ cd ..\n$ ls\n1 f\n$ cd /a\n$ ls\n2 g";
+        let (fs, diagnostics) = parse_strict(input);
+        assert_eq!(render(&diagnostics), [
+            "line 4: error: cd into unknown directory `b`",
+            "line 7: warning: `1 f` listed in an unknown directory, skipped",
+        ]);
+        assert_eq!(fs.size(), 2);
+        assert_eq!(fs, parse_input(input));
+        assert_eq!(fs.path_of(fs.current()).unwrap(), "/a");
+    }
+}
@@ -1,9 +1,106 @@
-use day7::{parse_input, part_1};
+use std::io::BufRead;
+use std::path::Path;
+
+use day7::{lint, parse_input, parse_lenient, parse_strict, part_1, Filesystem, Query, Severity, Shell};
 
 fn main() {
-    let input = std::fs::read_to_string("input.txt").unwrap();
-    let fs = parse_input(&*input);
+    let args: Vec<String> = std::env::args().collect();
+    let flag = |name: &str| args.iter().position(|arg| arg == name).map(|i| args.get(i + 1).expect("Invalid arguments"));
+
+    // `--scan <dir>` reads a real directory instead of the puzzle input, `--transcript` prints it as one.
+    // `--diff <before> <after>` compares two transcripts, `--json` for the machine-readable form.
+    if let Some(i) = args.iter().position(|arg| arg == "--diff") {
+        let read = |path: Option<&String>| parse_input(&std::fs::read_to_string(path.expect("Invalid arguments")).expect("Invalid transcript"));
+        let diff = read(args.get(i + 1)).diff(&read(args.get(i + 2)));
+        match args.iter().any(|arg| arg == "--json") {
+            true => println!("{}", diff.to_json()),
+            false => println!("{diff}"),
+        }
+        return;
+    }
+
+    // `--lint <file>` reports what looks wrong in a transcript and fails on errors.
+    if let Some(file) = flag("--lint") {
+        let diagnostics = lint(&std::fs::read_to_string(file).expect("Invalid transcript"));
+        diagnostics.iter().for_each(|diagnostic| println!("{file}: {diagnostic}"));
+        if diagnostics.iter().any(|diagnostic| diagnostic.severity() == Severity::Error) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `--lenient` repairs an inconsistent input.txt instead of skipping what doesn't apply.
+    let mut fs = match flag("--scan") {
+        Some(dir) => Filesystem::scan(Path::new(dir)).expect("Invalid directory"),
+        None => {
+            let input = std::fs::read_to_string("input.txt").unwrap();
+            let (fs, diagnostics) = match args.iter().any(|arg| arg == "--lenient") {
+                true => parse_lenient(&input),
+                false => parse_strict(&input),
+            };
+            diagnostics.iter().for_each(|diagnostic| eprintln!("input.txt: {diagnostic}"));
+            fs
+        }
+    };
+
+    // `--mounts <file>` mounts `<path> <capacity>` lines, `--capacity <size>` overrides the root volume's.
+    if let Some(table) = flag("--mounts") {
+        if let Err(error) = fs.mount_table(&std::fs::read_to_string(table).expect("Invalid mount table")) {
+            eprintln!("{table}: {error}");
+            return;
+        }
+    }
+    if let Some(capacity) = flag("--capacity") {
+        fs.set_capacity(capacity.replace('_', "").parse().expect("Invalid capacity"));
+    }
+
+    if args.iter().any(|arg| arg == "--df") {
+        println!("path\tcapacity\tused\tfree");
+        fs.volumes().iter().for_each(|volume| println!("{volume}"));
+        return;
+    }
+
+    if args.iter().any(|arg| arg == "--transcript") {
+        print!("{}", fs.transcript());
+        return;
+    }
+
+    // `--query "<query>"`, e.g. `glob /**/*.dat`, `largest 5`, `dirs ..=100000` or `list / 2`.
+    if let Some(query) = flag("--query") {
+        match query.parse::<Query>() {
+            Ok(query) => fs.query(&query).iter().for_each(|hit| println!("{hit}")),
+            Err(error) => eprintln!("{error}"),
+        }
+        return;
+    }
+
+    // `--cleanup <size>` plans the cheapest deletions freeing room for `size` on every volume.
+    if let Some(required) = flag("--cleanup") {
+        let cleanups = fs.cleanups(required.replace('_', "").parse().expect("Invalid size"));
+        println!("{}", cleanups.iter().map(|cleanup| cleanup.to_string()).collect::<Vec<_>>().join("\n\n"));
+        return;
+    }
+
+    // `--materialize <dir>` writes the tree out with sparse files.
+    if let Some(dir) = flag("--materialize") {
+        fs.materialize(Path::new(dir)).expect("Invalid directory");
+        return;
+    }
+
+    // `--shell` runs commands from stdin against the tree and prints the session transcript.
+    if args.iter().any(|arg| arg == "--shell") {
+        let mut shell = Shell::new(fs);
+        for line in std::io::stdin().lock().lines() {
+            let _ = shell.run(&line.expect("Invalid stdin"));
+        }
+        print!("{}", shell.transcript());
+        return;
+    }
+
     println!("{}", part_1(&fs, 100_000));
     println!("size = {}", fs.size());
-    println!("smallest dir to size 30_000_000 = {}", fs.smallest_dir(30_000_000).unwrap().size());
-}
+    match fs.smallest_dir(30_000_000) {
+        Some(smallest) => println!("smallest dir to size 30_000_000 = {}", fs.local_size(smallest).unwrap()),
+        None => println!("smallest dir to size 30_000_000 = none needed"),
+    }
+}
\ No newline at end of file
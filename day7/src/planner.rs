@@ -0,0 +1,426 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::{Filesystem, Hit, NodeId};
+
+// Word operations the exact search may spend before falling back to the heuristic.
+const EXACT_BUDGET: usize = 1 << 27;
+// Bytes the exact search may allocate, see `exact_memory`.
+const EXACT_MEMORY: usize = 1 << 27;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub deletions: Vec<Hit>,
+    pub freed: usize,
+    // `false` when the tree was too big for the exact search.
+    pub exact: bool,
+}
+
+// The best set of deletions on one volume next to the best single folder there,
+// `None` when even emptying the volume isn't enough. Folder sizes only count that volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cleanup {
+    pub volume: String,
+    pub needed: usize,
+    pub plan: Option<Plan>,
+    pub single: Option<Hit>,
+}
+
+impl fmt::Display for Cleanup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "need to free {} on {}", self.needed, self.volume)?;
+
+        let Some(plan) = &self.plan else { return write!(f, "not enough space even deleting everything") };
+        let method = if plan.exact { "exact" } else { "heuristic" };
+        writeln!(f, "plan frees {} ({method}) deleting {} entries:", plan.freed, plan.deletions.len())?;
+        for hit in &plan.deletions {
+            writeln!(f, "  {hit}")?;
+        }
+
+        match &self.single {
+            Some(single) => write!(f, "single directory: {single} ({} more)", single.size - plan.freed),
+            None => write!(f, "no deletion needed"),
+        }
+    }
+}
+
+// Smallest total of `sizes` that is at least `needed` and at most `bound`, as indexes into `sizes`.
+// A bitset of reachable totals is shifted by every size, each item keeps the totals it reached
+// first so the choice can be walked back. That's one entry per reachable total, not per total.
+fn subset_sum(sizes: &[usize], needed: usize, bound: usize) -> Option<Vec<usize>> {
+    assert!(bound <= u32::MAX as usize, "Invalid bound");
+    let words = bound / 64 + 1;
+    let mut reach = vec![0u64; words];
+    let mut fresh = vec![0u64; words];
+    let mut firsts: Vec<Vec<u32>> = vec![vec![]; sizes.len()];
+    reach[0] = 1;
+
+    for (i, &size) in sizes.iter().enumerate() {
+        if size > bound {
+            continue;
+        }
+
+        let (q, r) = (size / 64, size % 64);
+        for j in q..words {
+            let mut shifted = reach[j - q] << r;
+            if r > 0 && j > q {
+                shifted |= reach[j - q - 1] >> (64 - r);
+            }
+            fresh[j] = shifted & !reach[j];
+        }
+        fresh[words - 1] &= u64::MAX >> (63 - bound % 64);
+
+        for j in q..words {
+            let mut bits = fresh[j];
+            while bits != 0 {
+                firsts[i].push((j * 64 + bits.trailing_zeros() as usize) as u32);
+                bits &= bits - 1;
+            }
+            reach[j] |= fresh[j];
+        }
+    }
+
+    let mut total = (needed..=bound).find(|&total| reach[total / 64] & (1 << (total % 64)) != 0)?;
+    let mut chosen = vec![];
+    for i in (0..sizes.len()).rev() {
+        if total > 0 && firsts[i].binary_search(&(total as u32)).is_ok() {
+            chosen.push(i);
+            total -= sizes[i];
+        }
+    }
+
+    Some(chosen)
+}
+
+// What `subset_sum` allocates at most: two bitsets over every total up to `bound` and one `u32`
+// per reachable total, of which there are no more than `bound + 1` or `2^files`.
+fn exact_memory(files: usize, bound: usize) -> usize {
+    let totals = match u32::try_from(files).ok().and_then(|files| 1usize.checked_shl(files)) {
+        Some(subsets) => subsets.min(bound.saturating_add(1)),
+        None => bound.saturating_add(1),
+    };
+    (bound / 64 + 1).saturating_mul(2 * 8).saturating_add(totals.saturating_mul(4))
+}
+
+// Largest sizes that still fit under what's left to free, then the smallest one covering the rest.
+fn greedy(sizes: &[usize], needed: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| Reverse(sizes[i]));
+
+    let mut remaining = needed;
+    let mut chosen = vec![];
+    let mut left = vec![];
+
+    for i in order {
+        if remaining > 0 && sizes[i] <= remaining {
+            remaining -= sizes[i];
+            chosen.push(i);
+        } else {
+            left.push(i);
+        }
+    }
+
+    if remaining > 0 {
+        if let Some(&i) = left.iter().filter(|&&i| sizes[i] >= remaining).min_by_key(|&&i| sizes[i]) {
+            chosen.push(i);
+        }
+    }
+
+    chosen
+}
+
+impl Filesystem {
+    // Deleting a folder is deleting all of its files, so the search picks files and the
+    // result folds fully deleted folders back into one entry.
+    pub fn cleanup(&self, required: usize) -> Cleanup {
+        self.cleanup_in(self.root, required)
+    }
+
+    // Room for `required` bytes on the volume mounted at `volume`.
+    pub fn cleanup_in(&self, volume: NodeId, required: usize) -> Cleanup {
+        self.cleanup_with(volume, required, EXACT_BUDGET)
+    }
+
+    // One cleanup per volume, each needing room for `required`.
+    pub fn cleanups(&self, required: usize) -> Vec<Cleanup> {
+        self.volumes().into_iter().map(|volume| self.cleanup_in(volume.root, required)).collect()
+    }
+
+    fn cleanup_with(&self, volume: NodeId, required: usize, budget: usize) -> Cleanup {
+        let info = self.volume(volume).expect("Invalid volume");
+        let local: HashMap<NodeId, usize> = self.volume_sizes(volume).into_iter().collect();
+        let hit = |id: NodeId| Hit { path: self.path_of(id).unwrap(), size: local[&id], dir: true };
+
+        let needed = required.saturating_sub(info.free());
+        let smallest = self.smallest_dir_in(volume, required);
+        let single = smallest.map(hit);
+
+        if needed == 0 {
+            return Cleanup { volume: info.path, needed, plan: Some(Plan { deletions: vec![], freed: 0, exact: true }), single: None };
+        }
+        let Some(smallest) = smallest else {
+            return Cleanup { volume: info.path, needed, plan: None, single: None };
+        };
+        let bound = local[&smallest];
+
+        let files: Vec<(NodeId, usize)> = self.volume_folders(volume).into_iter()
+            .flat_map(|id| self.folder(id).unwrap().files().iter().enumerate().filter(|(_, file)| file.size() > 0).map(move |(i, _)| (id, i)))
+            .collect();
+        let sizes: Vec<usize> = files.iter().map(|&(id, i)| self.folder(id).unwrap().files()[i].size()).collect();
+
+        let exact = exact_memory(files.len(), bound) <= EXACT_MEMORY && files.len().saturating_mul(bound / 64 + 1) <= budget;
+        let mut chosen = if exact { subset_sum(&sizes, needed, bound).unwrap() } else { greedy(&sizes, needed) };
+        let mut freed: usize = chosen.iter().map(|&i| sizes[i]).sum();
+
+        // The heuristic can do worse than the single folder, then that folder's files are the plan.
+        if freed < needed || freed > bound {
+            let below: HashSet<NodeId> = self.volume_folders(smallest).into_iter().collect();
+            chosen = (0..files.len()).filter(|&i| below.contains(&files[i].0)).collect();
+            freed = bound;
+        }
+
+        let chosen: HashSet<(NodeId, usize)> = chosen.into_iter().map(|i| files[i]).collect();
+        let plan = Plan { deletions: self.fold_deletions(volume, &chosen, &local), freed, exact };
+
+        Cleanup { volume: info.path, needed, plan: Some(plan), single }
+    }
+
+    // Chosen files, with folders whose every byte is chosen listed once instead. The volume root
+    // and folders with a mount below them can't be removed as a whole, their entries are listed.
+    fn fold_deletions(&self, volume: NodeId, chosen: &HashSet<(NodeId, usize)>, local: &HashMap<NodeId, usize>) -> Vec<Hit> {
+        let order = self.volume_folders(volume);
+        let mut full = HashSet::new();
+
+        for &id in order.iter().rev() {
+            let folder = self.folder(id).unwrap();
+            let files = folder.files().iter().enumerate().all(|(i, file)| file.size() == 0 || chosen.contains(&(id, i)));
+            if id != volume && files && folder.folders().iter().all(|child| full.contains(child)) {
+                full.insert(id);
+            }
+        }
+
+        let mut deletions = vec![];
+        let mut pending = vec![volume];
+        while let Some(id) = pending.pop() {
+            if full.contains(&id) && local[&id] > 0 {
+                deletions.push(Hit { path: self.path_of(id).unwrap(), size: local[&id], dir: true });
+                continue;
+            }
+
+            let folder = self.folder(id).unwrap();
+            deletions.extend(folder.files().iter().enumerate()
+                .filter(|&(i, _)| chosen.contains(&(id, i)))
+                .map(|(_, file)| Hit { path: self.file_path(id, file.identifier()), size: file.size(), dir: false }));
+            pending.extend(folder.folders().iter().rev().filter(|&&child| !self.is_mount(child)));
+        }
+
+        deletions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::{EXAMPLE, Random};
+    use crate::planner::{exact_memory, greedy, subset_sum, EXACT_MEMORY};
+    use crate::{parse_input, File, Filesystem};
+
+    fn brute_force(sizes: &[usize], needed: usize) -> Option<usize> {
+        (0..1usize << sizes.len())
+            .map(|mask| (0..sizes.len()).filter(|i| mask & (1 << i) != 0).map(|i| sizes[i]).sum::<usize>())
+            .filter(|&total| total >= needed)
+            .min()
+    }
+
+    // `required` that leaves exactly `needed` bytes to free.
+    fn requiring(fs: &Filesystem, needed: usize) -> usize {
+        fs.capacity() - fs.size() + needed
+    }
+
+    #[test]
+    fn subset_sums() {
+        let mut random = Random(0xDEADBEEF);
+
+        for _ in 0..300 {
+            let sizes: Vec<usize> = (0..random.next(12) + 1).map(|_| random.next(500) + 1).collect();
+            let total: usize = sizes.iter().sum();
+            let needed = random.next(total) + 1;
+
+            let chosen = subset_sum(&sizes, needed, total).unwrap();
+            let freed: usize = chosen.iter().map(|&i| sizes[i]).sum();
+            assert_eq!(Some(freed), brute_force(&sizes, needed));
+            assert_eq!(chosen.len(), chosen.iter().collect::<std::collections::HashSet<_>>().len());
+
+            let greedy: usize = greedy(&sizes, needed).iter().map(|&i| sizes[i]).sum();
+            assert!(greedy >= needed);
+        }
+
+        assert_eq!(subset_sum(&[64, 64, 1], 129, 129), Some(vec![2, 1, 0]));
+        assert_eq!(subset_sum(&[100], 50, 99), None);
+    }
+
+    #[test]
+    fn example() {
+        let fs = parse_input(EXAMPLE);
+        let cleanup = fs.cleanup(30_000_000);
+        let plan = cleanup.plan.clone().unwrap();
+
+        let sizes: Vec<usize> = fs.query(&"glob /**".parse().unwrap()).into_iter().filter(|hit| !hit.dir).map(|hit| hit.size).collect();
+        assert_eq!(cleanup.needed, 8381165);
+        assert_eq!(Some(plan.freed), brute_force(&sizes, cleanup.needed));
+        assert_eq!(plan.deletions.iter().map(|hit| hit.size).sum::<usize>(), plan.freed);
+        assert!(plan.exact);
+
+        let single = cleanup.single.clone().unwrap();
+        assert_eq!((single.path.as_str(), single.size), ("/d", 24933642));
+        assert!(plan.freed <= single.size);
+        assert!(cleanup.to_string().starts_with("need to free 8381165 on /\nplan frees "));
+    }
+
+    #[test]
+    fn folds_whole_folders() {
+        let mut fs = Filesystem::new();
+        let x = fs.add_folder("x");
+        let inner = fs.add_folder_in(x, "inner");
+        fs.add_file_in(x, File::new("a", 5));
+        fs.add_file_in(inner, File::new("b", 5));
+        fs.add_file_in(inner, File::new("empty", 0));
+        let y = fs.add_folder("y");
+        fs.add_file_in(y, File::new("c", 3));
+        fs.add_file_in(y, File::new("d", 4));
+
+        let cleanup = fs.cleanup(requiring(&fs, 10));
+        let plan = cleanup.plan.unwrap();
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["10\t/x/"]);
+        assert_eq!(cleanup.single.unwrap().size, 10);
+
+        let plan = fs.cleanup(requiring(&fs, 8)).plan.unwrap();
+        assert_eq!(plan.freed, 8);
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["5\t/x/a", "3\t/y/c"]);
+
+        // The root itself is never a deletion, nor is a folder with a volume mounted below it.
+        let plan = fs.cleanup(requiring(&fs, 17)).plan.unwrap();
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["10\t/x/", "7\t/y/"]);
+
+        fs.mount("/x/inner", 100).unwrap();
+        let plan = fs.cleanup(requiring(&fs, 17)).plan.unwrap();
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["5\t/x/a", "7\t/y/"]);
+    }
+
+    #[test]
+    fn large_totals_use_the_heuristic() {
+        let mut fs = Filesystem::new().with_capacity(3_500_000_000);
+        fs.add_file(File::new("big", 3_000_000_000));
+        fs.add_file(File::new("small", 1));
+
+        let plan = fs.cleanup(1_000_000_000).plan.unwrap();
+        assert!(!plan.exact);
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["3000000000\t/big", "1\t/small"]);
+
+        // Every total up to 2^26 can be reached first by some file, a `u32` each on top of the bitsets.
+        assert_eq!(exact_memory(1_000, 1 << 26), (1 << 26) / 4 + 4 * ((1 << 26) + 1) + 16);
+        assert!(exact_memory(1_000, 1 << 26) > EXACT_MEMORY);
+        assert_eq!(exact_memory(10, 24_933_642), (24_933_642 / 64 + 1) * 16 + 4 * 1024);
+    }
+
+    #[test]
+    fn nothing_or_impossible() {
+        let fs = parse_input(EXAMPLE);
+
+        let nothing = fs.cleanup(1000);
+        assert_eq!(nothing.plan.unwrap().deletions, vec![]);
+        assert_eq!(nothing.single, None);
+
+        let impossible = fs.cleanup(fs.capacity() + 1);
+        assert_eq!(impossible.plan, None);
+        assert_eq!(impossible.single, None);
+    }
+
+    #[test]
+    fn heuristic_fallback() {
+        let mut random = Random(0xC0FFEE);
+
+        for _ in 0..50 {
+            let mut fs = Filesystem::new();
+            let mut ids = vec![fs.root()];
+            for i in 0..200 {
+                let at = ids[random.next(ids.len())];
+                if random.next(3) == 0 {
+                    ids.push(fs.add_folder_in(at, &format!("d{i}")));
+                } else {
+                    fs.add_file_in(at, File::new(&format!("f{i}"), random.next(10_000) + 1));
+                }
+            }
+
+            let required = requiring(&fs, random.next(fs.size()) + 1);
+            let exact = fs.cleanup(required);
+            let heuristic = fs.cleanup_with(fs.root(), required, 0);
+            let (exact_plan, heuristic_plan) = (exact.plan.unwrap(), heuristic.plan.unwrap());
+            let single = exact.single.unwrap().size;
+
+            assert!(exact_plan.exact && !heuristic_plan.exact);
+            assert!(exact.needed <= exact_plan.freed && exact_plan.freed <= heuristic_plan.freed);
+            assert!(heuristic_plan.freed <= single);
+            assert_eq!(heuristic_plan.deletions.iter().map(|hit| hit.size).sum::<usize>(), heuristic_plan.freed);
+            assert_eq!(exact_plan.deletions.iter().map(|hit| hit.size).sum::<usize>(), exact_plan.freed);
+        }
+    }
+
+    #[test]
+    fn input() {
+        let fs = parse_input(&std::fs::read_to_string("input.txt").unwrap());
+        let cleanup = fs.cleanup(30_000_000);
+        let plan = cleanup.plan.unwrap();
+        let single = cleanup.single.unwrap();
+
+        assert_eq!(single.size, 2086088);
+        assert!(cleanup.needed <= plan.freed && plan.freed <= single.size);
+        assert_eq!(plan.deletions.iter().map(|hit| hit.size).sum::<usize>(), plan.freed);
+
+        for a in &plan.deletions {
+            for b in &plan.deletions {
+                assert!(a == b || !b.path.starts_with(&format!("{}/", a.path)));
+            }
+        }
+    }
+
+    #[test]
+    fn per_volume() {
+        let mut fs = parse_input(EXAMPLE);
+        fs.mount_table("/ 40000000\n/d 25000000\n/a/e 600").unwrap();
+
+        let cleanups = fs.cleanups(1000);
+        assert_eq!(cleanups.iter().map(|cleanup| (cleanup.volume.as_str(), cleanup.needed)).collect::<Vec<_>>(), [
+            ("/", 0),
+            ("/a/e", 984),
+            ("/d", 0),
+        ]);
+        assert_eq!(cleanups[1].plan, None);
+
+        let root = fs.cleanup(16_600_000);
+        assert_eq!(root.needed, 16_600_000 - (40_000_000 - 23_446_939));
+        let single = root.single.unwrap();
+        assert_eq!((single.path.as_str(), single.size), ("/a", 94853 - 584));
+        let plan = root.plan.unwrap();
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["62596\t/a/h.lst"]);
+
+        let d = fs.resolve(fs.root(), "/d").unwrap();
+        let plan = fs.cleanup_in(d, 5_000_000).plan.unwrap();
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["5626152\t/d/d.ext"]);
+
+        fs.mount("/a", 100_000).unwrap();
+        let plan = fs.cleanup(40_000_000 - 94853 + 62596 + 29116).plan.unwrap();
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), ["14848514\t/b.txt", "8504156\t/c.dat"]);
+        fs.unmount("/a").unwrap();
+
+        // `/a` holds the `/a/e` mount, so its files go one by one.
+        let plan = fs.cleanup(40_000_000).plan.unwrap();
+        assert_eq!(plan.deletions.iter().map(|hit| hit.to_string()).collect::<Vec<_>>(), [
+            "14848514\t/b.txt",
+            "8504156\t/c.dat",
+            "29116\t/a/f",
+            "2557\t/a/g",
+            "62596\t/a/h.lst",
+        ]);
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+use std::str::FromStr;
+
+use crate::{Filesystem, NodeId};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Query {
+    // `path <path>`, the folder or file at that path.
+    Lookup(String),
+    // `glob <pattern>`, `*` and `?` within a name, `**` across any number of folders.
+    Glob(String),
+    // `largest <n>`, the n largest files.
+    Largest(usize),
+    // `dirs <range>`, folders whose size is in a Rust-style range like `..100000` or `1000..=5000`.
+    Dirs(Bound<usize>, Bound<usize>),
+    // `list <path> [depth]`, everything up to `depth` levels below the folder.
+    List { path: String, depth: usize },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum QueryError {
+    Empty,
+    UnknownQuery(String),
+    Usage(&'static str),
+    BadNumber(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use QueryError::*;
+
+        match self {
+            Empty => write!(f, "empty query"),
+            UnknownQuery(verb) => write!(f, "{verb}: unknown query"),
+            Usage(usage) => write!(f, "usage: {usage}"),
+            BadNumber(number) => write!(f, "{number}: not a number"),
+        }
+    }
+}
+
+// One match, folders carry their aggregate size.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Hit {
+    pub path: String,
+    pub size: usize,
+    pub dir: bool,
+}
+
+// `size<TAB>path` like `du`, folders end in `/`.
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let slash = if self.dir && self.path != "/" { "/" } else { "" };
+        write!(f, "{}\t{}{slash}", self.size, self.path)
+    }
+}
+
+fn number(text: &str) -> Result<usize, QueryError> {
+    text.parse().map_err(|_| QueryError::BadNumber(text.to_string()))
+}
+
+fn range(text: &str) -> Result<(Bound<usize>, Bound<usize>), QueryError> {
+    let (start, end) = text.split_once("..").ok_or(QueryError::Usage("dirs <min>..<max>"))?;
+    let start = if start.is_empty() { Bound::Unbounded } else { Bound::Included(number(start)?) };
+    let end = match end.strip_prefix('=') {
+        Some(end) => Bound::Included(number(end)?),
+        None if end.is_empty() => Bound::Unbounded,
+        None => Bound::Excluded(number(end)?),
+    };
+    Ok((start, end))
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        use Query::*;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let query = match words[..] {
+            [] => return Err(QueryError::Empty),
+            ["path", path] => Lookup(path.to_string()),
+            ["path", ..] => return Err(QueryError::Usage("path <path>")),
+            ["glob", pattern] => Glob(pattern.to_string()),
+            ["glob", ..] => return Err(QueryError::Usage("glob <pattern>")),
+            ["largest", n] => Largest(number(n)?),
+            ["largest", ..] => return Err(QueryError::Usage("largest <n>")),
+            ["dirs", bounds] => {
+                let (start, end) = range(bounds)?;
+                Dirs(start, end)
+            }
+            ["dirs", ..] => return Err(QueryError::Usage("dirs <min>..<max>")),
+            ["list", path] => List { path: path.to_string(), depth: 1 },
+            ["list", path, depth] => List { path: path.to_string(), depth: number(depth)? },
+            ["list", ..] => return Err(QueryError::Usage("list <path> [depth]")),
+            [verb, ..] => return Err(QueryError::UnknownQuery(verb.to_string())),
+        };
+
+        Ok(query)
+    }
+}
+
+// Shell-style match of one name against one pattern segment.
+fn matches_name(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// Whole-path match, `**` stands for zero or more segments.
+fn matches_path(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_path(rest, &path[skip..])),
+        Some((segment, rest)) => {
+            !path.is_empty() && matches_name(segment.as_bytes(), path[0].as_bytes()) && matches_path(rest, &path[1..])
+        }
+    }
+}
+
+impl Filesystem {
+    pub(crate) fn file_path(&self, dir: NodeId, name: &str) -> String {
+        match self.path_of(dir).unwrap().as_str() {
+            "/" => format!("/{name}"),
+            path => format!("{path}/{name}"),
+        }
+    }
+
+    pub(crate) fn folder_hit(&self, id: NodeId) -> Hit {
+        Hit { path: self.path_of(id).unwrap(), size: self.size_of(id), dir: true }
+    }
+
+    // Every folder and file below `id` down to `depth` levels, in tree order.
+    fn entries(&self, id: NodeId, depth: usize) -> Vec<Hit> {
+        let mut hits = vec![];
+        let mut pending = vec![(id, 0)];
+
+        while let Some((id, level)) = pending.pop() {
+            if level > 0 {
+                hits.push(self.folder_hit(id));
+            }
+            if level == depth {
+                continue;
+            }
+
+            let folder = self.folder(id).unwrap();
+            hits.extend(folder.files().iter().map(|file| Hit { path: self.file_path(id, file.identifier()), size: file.size(), dir: false }));
+            pending.extend(folder.folders().iter().rev().map(|&child| (child, level + 1)));
+        }
+
+        hits
+    }
+
+    pub fn query(&self, query: &Query) -> Vec<Hit> {
+        match query {
+            Query::Lookup(path) => {
+                if let Some(id) = self.resolve(self.root, path) {
+                    return vec![self.folder_hit(id)];
+                }
+                let (dir, name) = path.trim_end_matches('/').rsplit_once('/').unwrap_or(("", path));
+                let Some(dir) = self.resolve(self.root, dir) else { return vec![] };
+
+                self.folder(dir).unwrap().files().iter()
+                    .filter(|file| file.identifier() == name)
+                    .map(|file| Hit { path: self.file_path(dir, name), size: file.size(), dir: false })
+                    .collect()
+            }
+            Query::Glob(pattern) => {
+                let pattern: Vec<&str> = pattern.split('/').filter(|part| !part.is_empty()).collect();
+                let mut hits = vec![self.folder_hit(self.root)];
+                hits.extend(self.entries(self.root, usize::MAX));
+                hits.retain(|hit| {
+                    let path: Vec<&str> = hit.path.split('/').filter(|part| !part.is_empty()).collect();
+                    matches_path(&pattern, &path)
+                });
+                hits
+            }
+            Query::Largest(n) => {
+                let mut files: Vec<Hit> = self.entries(self.root, usize::MAX).into_iter().filter(|hit| !hit.dir).collect();
+                files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+                files.truncate(*n);
+                files
+            }
+            Query::Dirs(start, end) => {
+                self.sizes(self.root).into_iter()
+                    .filter(|(_, size)| (*start, *end).contains(size))
+                    .map(|(id, _)| self.folder_hit(id))
+                    .collect()
+            }
+            Query::List { path, depth } => match self.resolve(self.root, path) {
+                Some(id) => self.entries(id, *depth),
+                None => vec![],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Bound;
+
+    use crate::fixtures::EXAMPLE;
+    use crate::parse_input;
+    use crate::query::{matches_name, Hit, Query, QueryError};
+
+    fn run(query: &str) -> Vec<String> {
+        let fs = parse_input(EXAMPLE);
+        fs.query(&query.parse().unwrap()).iter().map(Hit::to_string).collect()
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!("dirs ..100000".parse(), Ok(Query::Dirs(Bound::Unbounded, Bound::Excluded(100000))));
+        assert_eq!("dirs 5..=9".parse(), Ok(Query::Dirs(Bound::Included(5), Bound::Included(9))));
+        assert_eq!("dirs 5..".parse(), Ok(Query::Dirs(Bound::Included(5), Bound::Unbounded)));
+        assert_eq!("list /a".parse(), Ok(Query::List { path: "/a".to_string(), depth: 1 }));
+        assert_eq!("dirs 5".parse::<Query>(), Err(QueryError::Usage("dirs <min>..<max>")));
+        assert_eq!("largest x".parse::<Query>(), Err(QueryError::BadNumber("x".to_string())));
+        assert_eq!("find x".parse::<Query>(), Err(QueryError::UnknownQuery("find".to_string())));
+        assert_eq!("".parse::<Query>(), Err(QueryError::Empty));
+    }
+
+    #[test]
+    fn names() {
+        assert!(matches_name(b"*.dat", b"c.dat"));
+        assert!(matches_name(b"*", b""));
+        assert!(matches_name(b"d.*", b"d.log"));
+        assert!(matches_name(b"?", b"k"));
+        assert!(matches_name(b"*a*b*", b"xxaxxbxx"));
+        assert!(!matches_name(b"*.dat", b"c.data"));
+        assert!(!matches_name(b"?", b""));
+        assert!(!matches_name(b"a*b", b"ab_"));
+    }
+
+    #[test]
+    fn lookups() {
+        assert_eq!(run("path /a/e"), ["584\t/a/e/"]);
+        assert_eq!(run("path /"), ["48381165\t/"]);
+        assert_eq!(run("path /d/k"), ["7214296\t/d/k"]);
+        assert_eq!(run("path b.txt"), ["14848514\t/b.txt"]);
+        assert!(run("path /a/nope").is_empty());
+        assert!(run("path /nope/k").is_empty());
+    }
+
+    #[test]
+    fn globs() {
+        assert_eq!(run("glob /**/*.dat"), ["8504156\t/c.dat"]);
+        assert_eq!(run("glob /d/d.*"), ["8033020\t/d/d.log", "5626152\t/d/d.ext"]);
+        assert_eq!(run("glob /a/**"), ["94853\t/a/", "29116\t/a/f", "2557\t/a/g", "62596\t/a/h.lst", "584\t/a/e/", "584\t/a/e/i"]);
+        assert_eq!(run("glob /a/**/?"), ["29116\t/a/f", "2557\t/a/g", "584\t/a/e/", "584\t/a/e/i"]);
+        assert_eq!(run("glob /*/e"), ["584\t/a/e/"]);
+        assert_eq!(run("glob /"), ["48381165\t/"]);
+        assert_eq!(run("glob /**").len(), 14);
+        assert!(run("glob /x/**").is_empty());
+    }
+
+    #[test]
+    fn largest_and_ranges() {
+        assert_eq!(run("largest 3"), ["14848514\t/b.txt", "8504156\t/c.dat", "8033020\t/d/d.log"]);
+        assert_eq!(run("largest 100").len(), 10);
+        assert!(run("largest 0").is_empty());
+
+        assert_eq!(run("dirs ..=100000"), ["94853\t/a/", "584\t/a/e/"]);
+        assert_eq!(run("dirs 584..584"), Vec::<String>::new());
+        assert_eq!(run("dirs 584..=584"), ["584\t/a/e/"]);
+        assert_eq!(run("dirs 1000000.."), ["48381165\t/", "24933642\t/d/"]);
+    }
+
+    #[test]
+    fn listings() {
+        assert_eq!(run("list /"), ["14848514\t/b.txt", "8504156\t/c.dat", "94853\t/a/", "24933642\t/d/"]);
+        assert_eq!(run("list /a 2"), ["29116\t/a/f", "2557\t/a/g", "62596\t/a/h.lst", "584\t/a/e/", "584\t/a/e/i"]);
+        assert_eq!(run("list /a 0"), Vec::<String>::new());
+        assert!(run("list /nope").is_empty());
+    }
+
+    #[test]
+    fn matches_part_1() {
+        let fs = parse_input(&std::fs::read_to_string("input.txt").unwrap());
+        let hits = fs.query(&"dirs ..=100000".parse().unwrap());
+        assert_eq!(hits.iter().map(|hit| hit.size).sum::<usize>(), crate::part_1(&fs, 100_000));
+        assert_eq!(hits.len(), fs.folders_within(100_000).len());
+    }
+}
@@ -0,0 +1,522 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Filesystem, NodeId};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Command {
+    Cd(String),
+    Ls(Option<String>),
+    Pwd,
+    Mkdir { parents: bool, path: String },
+    Rm { recursive: bool, path: String },
+    Du { summarize: bool, path: Option<String> },
+    Tree(Option<String>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ShellError {
+    UnknownCommand(String),
+    Usage(&'static str),
+    NotFound(String),
+    NotADirectory(String),
+    IsADirectory(String),
+    Exists(String),
+    RootRemoval,
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ShellError::*;
+
+        match self {
+            UnknownCommand(verb) => write!(f, "{verb}: command not found"),
+            Usage(usage) => write!(f, "usage: {usage}"),
+            NotFound(path) => write!(f, "{path}: No such file or directory"),
+            NotADirectory(path) => write!(f, "{path}: Not a directory"),
+            IsADirectory(path) => write!(f, "{path}: Is a directory"),
+            Exists(path) => write!(f, "{path}: File exists"),
+            RootRemoval => write!(f, "refusing to remove '/'"),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = ShellError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        use Command::*;
+
+        let mut words = line.split_whitespace();
+        let verb = words.next().unwrap_or_default();
+        let (flags, args): (Vec<&str>, Vec<&str>) = words.partition(|word| word.starts_with('-') && word.len() > 1);
+        let path = args.first().map(|path| path.to_string());
+
+        let command = match (verb, &flags[..], args.len()) {
+            ("cd", [], 1) => Cd(path.unwrap()),
+            ("cd", ..) => return Err(ShellError::Usage("cd <path>")),
+            ("ls", [], 0 | 1) => Ls(path),
+            ("ls", ..) => return Err(ShellError::Usage("ls [path]")),
+            ("pwd", [], 0) => Pwd,
+            ("pwd", ..) => return Err(ShellError::Usage("pwd")),
+            ("mkdir", [] | ["-p"], 1) => Mkdir { parents: !flags.is_empty(), path: path.unwrap() },
+            ("mkdir", ..) => return Err(ShellError::Usage("mkdir [-p] <path>")),
+            ("rm", [] | ["-r" | "-R"], 1) => Rm { recursive: !flags.is_empty(), path: path.unwrap() },
+            ("rm", ..) => return Err(ShellError::Usage("rm [-r] <path>")),
+            ("du", [] | ["-s"], 0 | 1) => Du { summarize: !flags.is_empty(), path },
+            ("du", ..) => return Err(ShellError::Usage("du [-s] [path]")),
+            ("tree", [], 0 | 1) => Tree(path),
+            ("tree", ..) => return Err(ShellError::Usage("tree [path]")),
+            _ => return Err(ShellError::UnknownCommand(verb.to_string())),
+        };
+
+        Ok(command)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Command::*;
+
+        match self {
+            Cd(path) => write!(f, "cd {path}"),
+            Ls(None) => write!(f, "ls"),
+            Ls(Some(path)) => write!(f, "ls {path}"),
+            Pwd => write!(f, "pwd"),
+            Mkdir { parents, path } => write!(f, "mkdir {}{path}", if *parents { "-p " } else { "" }),
+            Rm { recursive, path } => write!(f, "rm {}{path}", if *recursive { "-r " } else { "" }),
+            Du { summarize, path } => {
+                write!(f, "du")?;
+                if *summarize {
+                    write!(f, " -s")?;
+                }
+                path.iter().try_for_each(|path| write!(f, " {path}"))
+            }
+            Tree(None) => write!(f, "tree"),
+            Tree(Some(path)) => write!(f, "tree {path}"),
+        }
+    }
+}
+
+impl Filesystem {
+    // Follows `path` from `from`, or from the root when it starts with `/`. `..` stops at the root.
+    pub fn resolve(&self, from: NodeId, path: &str) -> Option<NodeId> {
+        let mut at = if path.starts_with('/') { self.root } else { from };
+        self.folder(at)?;
+
+        for part in path.split('/').filter(|part| !part.is_empty()) {
+            at = match part {
+                "." => at,
+                ".." => self.parent(at).unwrap_or(self.root),
+                name => self.child(at, name)?,
+            };
+        }
+
+        Some(at)
+    }
+
+    // `None` for a folder that was removed.
+    pub fn path_of(&self, mut id: NodeId) -> Option<String> {
+        self.folder(id)?;
+        let mut parts = vec![];
+        while let Some(outer) = self.parent(id) {
+            parts.push(self.folder(id).unwrap().identifier());
+            id = outer;
+        }
+        parts.reverse();
+        Some(format!("/{}", parts.join("/")))
+    }
+
+    // Splits `path` into the folder holding its last component and that component.
+    fn locate<'a>(&self, path: &'a str) -> Result<(NodeId, &'a str), ShellError> {
+        let trimmed = path.trim_end_matches('/');
+        let (dir, name) = match trimmed.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((dir, name)) => (dir, name),
+            None => (".", trimmed),
+        };
+
+        self.resolve(self.current, dir).map(|dir| (dir, name)).ok_or_else(|| ShellError::NotFound(path.to_string()))
+    }
+
+    fn has_file(&self, dir: NodeId, name: &str) -> bool {
+        self.folder(dir).unwrap().files().iter().any(|file| file.identifier() == name)
+    }
+
+    pub(crate) fn resolve_dir(&self, path: Option<&str>) -> Result<NodeId, ShellError> {
+        let Some(path) = path else { return Ok(self.current) };
+
+        self.resolve(self.current, path).ok_or_else(|| match self.locate(path) {
+            Ok((dir, name)) if self.has_file(dir, name) => ShellError::NotADirectory(path.to_string()),
+            _ => ShellError::NotFound(path.to_string()),
+        })
+    }
+
+    // Runs one shell command against the tree and returns what it prints.
+    pub fn execute(&mut self, line: &str) -> Result<String, ShellError> {
+        use Command::*;
+        self.listing = None;
+
+        match line.parse::<Command>()? {
+            Cd(path) => self.cd(&path).map(|_| String::new()),
+            Ls(path) => {
+                let dir = self.resolve_dir(path.as_deref())?;
+                self.listing = Some(dir);
+                Ok(self.ls(dir).unwrap())
+            }
+            Pwd => Ok(self.path_of(self.current).unwrap()),
+            Mkdir { parents, path } => self.mkdir(&path, parents).map(|_| String::new()),
+            Rm { recursive, path } => self.rm(&path, recursive).map(|_| String::new()),
+            Du { summarize, path } => {
+                let dir = self.resolve_dir(path.as_deref())?;
+                Ok(self.du(dir, summarize).unwrap())
+            }
+            Tree(path) => {
+                let dir = self.resolve_dir(path.as_deref())?;
+                Ok(self.tree(dir).unwrap())
+            }
+        }
+    }
+
+    pub(crate) fn mkdir(&mut self, path: &str, parents: bool) -> Result<NodeId, ShellError> {
+        if parents {
+            let mut at = if path.starts_with('/') { self.root } else { self.current };
+            for part in path.split('/').filter(|part| !part.is_empty()) {
+                at = match part {
+                    "." => at,
+                    ".." => self.parent(at).unwrap_or(self.root),
+                    name if self.has_file(at, name) => return Err(ShellError::NotADirectory(path.to_string())),
+                    name => self.add_folder_in(at, name),
+                };
+            }
+            return Ok(at);
+        }
+
+        let (dir, name) = self.locate(path)?;
+        if matches!(name, "" | "." | "..") || self.child(dir, name).is_some() || self.has_file(dir, name) {
+            return Err(ShellError::Exists(path.to_string()));
+        }
+
+        Ok(self.add_folder_in(dir, name))
+    }
+
+    fn rm(&mut self, path: &str, recursive: bool) -> Result<(), ShellError> {
+        if let Some(target) = self.resolve(self.current, path) {
+            if target == self.root {
+                return Err(ShellError::RootRemoval);
+            }
+            if !recursive {
+                return Err(ShellError::IsADirectory(path.to_string()));
+            }
+            self.remove_node(target);
+            return Ok(());
+        }
+
+        let (dir, name) = self.locate(path)?;
+        if !self.remove_file_in(dir, name) {
+            return Err(ShellError::NotFound(path.to_string()));
+        }
+        Ok(())
+    }
+
+    // Puzzle-style listing, folders first. `ls`, `du` and `tree` give `None` for a removed folder.
+    pub fn ls(&self, dir: NodeId) -> Option<String> {
+        let folder = self.folder(dir)?;
+        let folders = folder.folders().iter().map(|&child| format!("dir {}", self.folder(child).unwrap().identifier()));
+        let files = folder.files().iter().map(|file| format!("{} {}", file.size(), file.identifier()));
+
+        Some(folders.chain(files).collect::<Vec<_>>().join("\n"))
+    }
+
+    // `size<TAB>path` for `dir`, or for every folder below it deepest first.
+    pub fn du(&self, dir: NodeId, summarize: bool) -> Option<String> {
+        let path = self.path_of(dir)?;
+        let sizes = self.sizes(dir);
+
+        if summarize {
+            Some(format!("{}\t{path}", sizes[0].1))
+        } else {
+            Some(sizes.iter().rev().map(|&(id, size)| format!("{size}\t{}", self.path_of(id).unwrap())).collect::<Vec<_>>().join("\n"))
+        }
+    }
+
+    // The puzzle's tree drawing, entries sorted by name.
+    pub fn tree(&self, dir: NodeId) -> Option<String> {
+        self.folder(dir)?;
+        enum Entry<'a> {
+            Folder(NodeId),
+            File(&'a crate::File),
+        }
+
+        let mut lines = vec![];
+        let mut pending = vec![(0, Entry::Folder(dir))];
+
+        while let Some((depth, entry)) = pending.pop() {
+            let id = match entry {
+                Entry::Folder(id) => id,
+                Entry::File(file) => {
+                    lines.push(format!("{}- {} (file, size={})", "  ".repeat(depth), file.identifier(), file.size()));
+                    continue;
+                }
+            };
+
+            let folder = self.folder(id).unwrap();
+            lines.push(format!("{}- {} (dir)", "  ".repeat(depth), folder.identifier()));
+
+            let mut entries: Vec<(&str, Entry)> = folder.folders().iter().map(|&id| (self.folder(id).unwrap().identifier(), Entry::Folder(id)))
+                .chain(folder.files().iter().map(|file| (file.identifier(), Entry::File(file))))
+                .collect();
+            entries.sort_by_key(|&(name, _)| name);
+            pending.extend(entries.into_iter().rev().map(|(_, entry)| (depth + 1, entry)));
+        }
+
+        Some(lines.join("\n"))
+    }
+
+    // A `cd`/`ls` walk of the whole tree that parses back into it.
+    pub fn transcript(&self) -> String {
+        let mut out = String::from("$ cd /\n");
+        // `Some` lists a folder that was just entered, `None` goes back up from one.
+        let mut pending = vec![Some(self.root)];
+
+        while let Some(step) = pending.pop() {
+            let Some(dir) = step else {
+                out.push_str("$ cd ..\n");
+                continue;
+            };
+
+            if dir != self.root {
+                out.push_str(&format!("$ cd {}\n", self.folder(dir).unwrap().identifier()));
+            }
+            out.push_str("$ ls\n");
+            let listing = self.ls(dir).unwrap();
+            if !listing.is_empty() {
+                out.push_str(&listing);
+                out.push('\n');
+            }
+
+            for &child in self.folder(dir).unwrap().folders().iter().rev() {
+                pending.push(None);
+                pending.push(Some(child));
+            }
+        }
+
+        out
+    }
+
+    // Same folders and files by name, regardless of ids or insertion order.
+    fn same_tree(&self, a: NodeId, other: &Filesystem, b: NodeId) -> bool {
+        let mut pending = vec![(a, b)];
+
+        while let Some((a, b)) = pending.pop() {
+            let (left, right) = (self.folder(a).unwrap(), other.folder(b).unwrap());
+
+            let mut left_files: Vec<_> = left.files().iter().map(|file| (file.identifier(), file.size())).collect();
+            let mut right_files: Vec<_> = right.files().iter().map(|file| (file.identifier(), file.size())).collect();
+            left_files.sort();
+            right_files.sort();
+
+            if left_files != right_files || left.folders().len() != right.folders().len() {
+                return false;
+            }
+
+            for &child in left.folders() {
+                match other.child(b, self.folder(child).unwrap().identifier()) {
+                    Some(matching) => pending.push((child, matching)),
+                    None => return false,
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl PartialEq for Filesystem {
+    fn eq(&self, other: &Self) -> bool {
+        self.same_tree(self.root, other, other.root)
+    }
+}
+
+impl Eq for Filesystem {}
+
+impl fmt::Debug for Filesystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.tree(self.root).unwrap())
+    }
+}
+
+// Interactive front end over a `Filesystem` that records a transcript of the session.
+pub struct Shell {
+    fs: Filesystem,
+    transcript: String,
+}
+
+impl Shell {
+    // Sessions start at the root, like transcripts do when parsed.
+    pub fn new(mut fs: Filesystem) -> Self {
+        fs.current = fs.root;
+        Shell { fs, transcript: String::new() }
+    }
+
+    pub fn run(&mut self, line: &str) -> Result<String, ShellError> {
+        let result = self.fs.execute(line);
+        let verb = line.split_whitespace().next().unwrap_or_default();
+
+        self.transcript.push_str(&format!("$ {}\n", line.trim()));
+        match &result {
+            Ok(output) if output.is_empty() => (),
+            Ok(output) => self.transcript.push_str(&format!("{output}\n")),
+            Err(error @ ShellError::UnknownCommand(_)) => self.transcript.push_str(&format!("{error}\n")),
+            Err(error) => self.transcript.push_str(&format!("{verb}: {error}\n")),
+        }
+
+        result
+    }
+
+    pub fn filesystem(&self) -> &Filesystem {
+        &self.fs
+    }
+
+    pub fn into_filesystem(self) -> Filesystem {
+        self.fs
+    }
+
+    pub fn transcript(&self) -> &str {
+        &self.transcript
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::EXAMPLE;
+    use crate::{parse_input, Command, Filesystem, Shell, ShellError};
+
+    #[test]
+    fn commands_round_trip() {
+        for line in ["cd /a/b", "ls", "ls ../x", "pwd", "mkdir -p a/b", "mkdir a", "rm -r a", "rm f", "du", "du -s /a", "tree", "tree d"] {
+            let command: Command = line.parse().unwrap();
+            assert_eq!(command.to_string(), line);
+        }
+
+        assert_eq!("cd".parse::<Command>(), Err(ShellError::Usage("cd <path>")));
+        assert_eq!("mkdir -x a".parse::<Command>(), Err(ShellError::Usage("mkdir [-p] <path>")));
+        assert_eq!("echo hi".parse::<Command>(), Err(ShellError::UnknownCommand("echo".to_string())));
+        assert_eq!("rm -R a".parse::<Command>(), Ok(Command::Rm { recursive: true, path: "a".to_string() }));
+    }
+
+    #[test]
+    fn paths() {
+        let mut shell = Shell::new(parse_input(EXAMPLE));
+
+        assert_eq!(shell.run("pwd"), Ok("/".to_string()));
+        shell.run("cd a/e").unwrap();
+        assert_eq!(shell.run("pwd"), Ok("/a/e".to_string()));
+        shell.run("cd ../../d").unwrap();
+        assert_eq!(shell.run("pwd"), Ok("/d".to_string()));
+        shell.run("cd /a/./e/").unwrap();
+        assert_eq!(shell.run("pwd"), Ok("/a/e".to_string()));
+        shell.run("cd /").unwrap();
+        shell.run("cd ../..").unwrap();
+        assert_eq!(shell.run("pwd"), Ok("/".to_string()));
+
+        assert_eq!(shell.run("cd nope"), Err(ShellError::NotFound("nope".to_string())));
+        assert_eq!(shell.run("cd b.txt"), Err(ShellError::NotADirectory("b.txt".to_string())));
+        assert_eq!(shell.run("ls /a/e/i"), Err(ShellError::NotADirectory("/a/e/i".to_string())));
+        assert_eq!(shell.run("pwd"), Ok("/".to_string()));
+    }
+
+    #[test]
+    fn listings() {
+        let mut shell = Shell::new(parse_input(EXAMPLE));
+
+        assert_eq!(shell.run("ls"), Ok("dir a\ndir d\n14848514 b.txt\n8504156 c.dat".to_string()));
+        assert_eq!(shell.run("ls a/e"), Ok("584 i".to_string()));
+        assert_eq!(shell.run("du -s"), Ok("48381165\t/".to_string()));
+        assert_eq!(shell.run("du -s /a"), Ok("94853\t/a".to_string()));
+        assert_eq!(shell.run("du"), Ok("24933642\t/d\n584\t/a/e\n94853\t/a\n48381165\t/".to_string()));
+
+        let tree = "- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)";
+        assert_eq!(shell.run("tree"), Ok(tree.to_string()));
+        assert_eq!(shell.run("tree a/e"), Ok("- e (dir)\n  - i (file, size=584)".to_string()));
+    }
+
+    #[test]
+    fn mkdir_and_rm() {
+        let mut shell = Shell::new(parse_input(EXAMPLE));
+
+        assert_eq!(shell.run("mkdir x/y"), Err(ShellError::NotFound("x/y".to_string())));
+        assert_eq!(shell.run("mkdir -p x/y/z"), Ok(String::new()));
+        assert_eq!(shell.run("mkdir -p x/y"), Ok(String::new()));
+        assert_eq!(shell.run("mkdir x"), Err(ShellError::Exists("x".to_string())));
+        assert_eq!(shell.run("mkdir b.txt"), Err(ShellError::Exists("b.txt".to_string())));
+        assert_eq!(shell.run("mkdir -p b.txt/q"), Err(ShellError::NotADirectory("b.txt/q".to_string())));
+        assert_eq!(shell.run("mkdir /a/w"), Ok(String::new()));
+        assert_eq!(shell.run("ls a"), Ok("dir e\ndir w\n29116 f\n2557 g\n62596 h.lst".to_string()));
+
+        shell.run("cd x/y/z").unwrap();
+        assert_eq!(shell.run("rm /x"), Err(ShellError::IsADirectory("/x".to_string())));
+        assert_eq!(shell.run("rm -r /"), Err(ShellError::RootRemoval));
+        let y = shell.filesystem().resolve(shell.filesystem().root(), "/x/y").unwrap();
+        assert_eq!(shell.run("rm -r /x"), Ok(String::new()));
+        assert_eq!(shell.run("pwd"), Ok("/".to_string()));
+
+        // Ids of removed folders are no longer usable.
+        let fs = shell.filesystem();
+        assert_eq!(fs.path_of(y), None);
+        assert_eq!((fs.ls(y), fs.du(y, true), fs.tree(y)), (None, None, None));
+        assert_eq!((fs.local_size(y), fs.volume_of(y)), (None, None));
+
+        assert_eq!(shell.run("rm a/f"), Ok(String::new()));
+        assert_eq!(shell.run("rm a/f"), Err(ShellError::NotFound("a/f".to_string())));
+        assert_eq!(shell.run("du -s a"), Ok(format!("{}\t/a", 94853 - 29116)));
+        assert_eq!(shell.filesystem().len(), 5);
+    }
+
+    #[test]
+    fn transcripts_reparse() {
+        let mut shell = Shell::new(parse_input(EXAMPLE));
+        let script = [
+            "ls", "cd a", "ls", "mkdir -p new/deeper", "cd new", "ls", "cd /", "pwd", "tree", "du",
+            "rm -r d", "rm -r d", "cd d", "ls d", "echo hi", "mkdir", "rm b.txt", "cd /a/e", "ls", "du -s ..", "cd ../new/deeper", "ls /",
+        ];
+        for line in script {
+            let _ = shell.run(line);
+        }
+
+        let reparsed = parse_input(shell.transcript());
+        let mut expected = parse_input(EXAMPLE);
+        expected.execute("mkdir -p /a/new/deeper").unwrap();
+        expected.execute("rm -r /d").unwrap();
+        expected.execute("rm /b.txt").unwrap();
+
+        assert_eq!(shell.filesystem(), &expected);
+        assert_eq!(reparsed, expected);
+        assert_eq!(parse_input(&reparsed.transcript()), expected);
+        assert_ne!(parse_input(EXAMPLE), expected);
+    }
+
+    #[test]
+    fn input_transcript() {
+        let input = std::fs::read_to_string("input.txt").unwrap();
+        let fs = parse_input(&input);
+        let transcript = fs.transcript();
+
+        assert_eq!(parse_input(&transcript), fs);
+        assert_eq!(parse_input(&transcript).transcript(), transcript);
+        assert_eq!(parse_input(&Filesystem::new().transcript()), Filesystem::new());
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+
+use crate::{Filesystem, NodeId};
+
+// A folder with a capacity of its own, everything below it that isn't under another mount lives on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    pub root: NodeId,
+    pub path: String,
+    pub capacity: usize,
+    pub used: usize,
+}
+
+impl Volume {
+    pub fn free(&self) -> usize {
+        self.capacity.saturating_sub(self.used)
+    }
+}
+
+// `path<TAB>capacity<TAB>used<TAB>free`, a `df` row.
+impl fmt::Display for Volume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}\t{}", self.path, self.capacity, self.used, self.free())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MountError {
+    NotFound(String),
+    NotMounted(String),
+    RootUnmount,
+    Malformed { line: usize, text: String },
+}
+
+impl fmt::Display for MountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MountError::*;
+
+        match self {
+            NotFound(path) => write!(f, "{path}: No such directory"),
+            NotMounted(path) => write!(f, "{path}: not a mount point"),
+            RootUnmount => write!(f, "the root volume can't be unmounted"),
+            Malformed { line, text } => write!(f, "line {line}: expected `<path> <capacity>`, got `{text}`"),
+        }
+    }
+}
+
+impl Filesystem {
+    pub fn capacity(&self) -> usize {
+        self.folder(self.root).unwrap().capacity.unwrap()
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        let root = self.root;
+        self.folder_mut(root).capacity = Some(capacity);
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.set_capacity(capacity);
+        self
+    }
+
+    // Makes the folder at `path` a volume, mounting over an existing mount just changes its capacity.
+    pub fn mount(&mut self, path: &str, capacity: usize) -> Result<NodeId, MountError> {
+        let id = self.resolve(self.root, path).ok_or_else(|| MountError::NotFound(path.to_string()))?;
+        self.folder_mut(id).capacity = Some(capacity);
+        Ok(id)
+    }
+
+    pub fn unmount(&mut self, path: &str) -> Result<(), MountError> {
+        let id = self.resolve(self.root, path).ok_or_else(|| MountError::NotFound(path.to_string()))?;
+        if id == self.root {
+            return Err(MountError::RootUnmount);
+        }
+        self.folder_mut(id).capacity.take().map(|_| ()).ok_or_else(|| MountError::NotMounted(path.to_string()))
+    }
+
+    // Mounts every `<path> <capacity>` line, `#` starts a comment and `/` sets the root capacity.
+    pub fn mount_table(&mut self, table: &str) -> Result<(), MountError> {
+        for (i, line) in table.lines().enumerate() {
+            let text = line.split('#').next().unwrap().trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let malformed = || MountError::Malformed { line: i + 1, text: text.to_string() };
+            let [path, capacity] = text.split_whitespace().collect::<Vec<_>>()[..] else { return Err(malformed()) };
+            let capacity = capacity.replace('_', "").parse().map_err(|_| malformed())?;
+            self.mount(path, capacity)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn is_mount(&self, id: NodeId) -> bool {
+        self.folder(id).is_some_and(|folder| folder.capacity.is_some())
+    }
+
+    // The volume `id` lives on, `None` for a removed folder.
+    pub fn volume_of(&self, mut id: NodeId) -> Option<NodeId> {
+        while !self.is_mount(id) {
+            id = self.parent(id)?;
+        }
+        Some(id)
+    }
+
+    // Folders on the volume mounted at `volume`, parents first, nested volumes left out.
+    pub(crate) fn volume_folders(&self, volume: NodeId) -> Vec<NodeId> {
+        let mut order = vec![];
+        let mut stack = vec![volume];
+
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.folder(id).unwrap().folders.iter().rev().filter(|&&child| !self.is_mount(child)));
+        }
+
+        order
+    }
+
+    // Bytes each folder of the volume holds on that volume, in `volume_folders` order.
+    pub(crate) fn volume_sizes(&self, volume: NodeId) -> Vec<(NodeId, usize)> {
+        let order = self.volume_folders(volume);
+        if order.len() == self.descendants(volume).len() {
+            return order.into_iter().map(|id| (id, self.size_of(id))).collect();
+        }
+
+        let mut sizes = vec![0; self.nodes.len()];
+        for &id in order.iter().rev() {
+            let folder = self.folder(id).unwrap();
+            let below: usize = folder.folders.iter().filter(|&&child| !self.is_mount(child)).map(|child| sizes[child.0]).sum();
+            sizes[id.0] = folder.files_size() + below;
+        }
+
+        order.into_iter().map(|id| (id, sizes[id.0])).collect()
+    }
+
+    // Size of `id` without the volumes mounted below it, `None` for a removed folder.
+    pub fn local_size(&self, id: NodeId) -> Option<usize> {
+        self.folder(id)?;
+        Some(self.volume_sizes(id)[0].1)
+    }
+
+    pub fn volume(&self, id: NodeId) -> Option<Volume> {
+        let capacity = self.folder(id)?.capacity?;
+        Some(Volume { root: id, path: self.path_of(id)?, capacity, used: self.local_size(id)? })
+    }
+
+    // Every volume, the root first.
+    pub fn volumes(&self) -> Vec<Volume> {
+        self.descendants(self.root).into_iter().filter_map(|id| self.volume(id)).collect()
+    }
+
+    // Smallest folder on the volume whose deletion leaves room for `size` there.
+    pub fn smallest_dir_in(&self, volume: NodeId, size: usize) -> Option<NodeId> {
+        let volume = self.volume(volume)?;
+
+        if volume.used + size <= volume.capacity {
+            None
+        } else {
+            let needed = size - volume.free();
+            self.volume_sizes(volume.root).into_iter().filter(|&(_, size)| size >= needed).min_by_key(|&(_, size)| size).map(|(id, _)| id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::EXAMPLE;
+    use crate::{parse_input, Filesystem, MountError};
+
+    fn rows(fs: &Filesystem) -> Vec<String> {
+        fs.volumes().iter().map(|volume| volume.to_string()).collect()
+    }
+
+    #[test]
+    fn capacity() {
+        let fs = parse_input(EXAMPLE);
+        assert_eq!(fs.capacity(), 70_000_000);
+        assert_eq!(fs.size_of(fs.smallest_dir(30_000_000).unwrap()), 24933642);
+
+        let fs = parse_input(EXAMPLE).with_capacity(50_000_000);
+        assert_eq!(rows(&fs), ["/\t50000000\t48381165\t1618835"]);
+        assert_eq!(fs.path_of(fs.smallest_dir(10_000_000).unwrap()).unwrap(), "/d");
+        assert_eq!(fs.smallest_dir(1_618_835), None);
+        assert_eq!(fs.path_of(fs.smallest_dir(1_618_836).unwrap()).unwrap(), "/a/e");
+    }
+
+    #[test]
+    fn mounts() {
+        let mut fs = parse_input(EXAMPLE);
+        let d = fs.mount("/d", 30_000_000).unwrap();
+        fs.mount("a/e", 1000).unwrap();
+
+        assert_eq!(rows(&fs), [
+            "/\t70000000\t23446939\t46553061",
+            "/a/e\t1000\t584\t416",
+            "/d\t30000000\t24933642\t5066358",
+        ]);
+        assert_eq!(fs.volume_of(fs.resolve(fs.root(), "/a/e").unwrap()), fs.resolve(fs.root(), "/a/e"));
+        assert_eq!(fs.volume_of(fs.resolve(fs.root(), "/a").unwrap()), Some(fs.root()));
+        assert_eq!(fs.volume_of(d), Some(d));
+
+        assert_eq!(fs.smallest_dir(30_000_000), None);
+        assert_eq!(fs.smallest_dir_in(d, 5_066_358), None);
+        assert_eq!(fs.smallest_dir_in(d, 6_000_000), Some(d));
+        assert_eq!(fs.path_of(fs.smallest_dir_in(fs.volume_of(d).unwrap(), 6_000_000).unwrap()).unwrap(), "/d");
+
+        let e = fs.resolve(fs.root(), "/a/e").unwrap();
+        assert_eq!(fs.smallest_dir_in(e, 500), Some(e));
+        assert_eq!(fs.smallest_dir_in(fs.root(), 46_600_000).and_then(|id| fs.path_of(id)), Some("/a".to_string()));
+
+        assert_eq!(fs.local_size(fs.root()), Some(23446939));
+        assert_eq!(fs.local_size(fs.resolve(fs.root(), "/a").unwrap()), Some(94853 - 584));
+
+        fs.unmount("/d").unwrap();
+        assert_eq!(fs.unmount("/d"), Err(MountError::NotMounted("/d".to_string())));
+        assert_eq!(fs.unmount("/"), Err(MountError::RootUnmount));
+        assert_eq!(fs.volumes().len(), 2);
+        assert_eq!(fs.volume(fs.root()).unwrap().used, 48381165 - 584);
+    }
+
+    #[test]
+    fn tables() {
+        let mut fs = parse_input(EXAMPLE);
+        fs.mount_table("# path capacity\n/ 60_000_000\n\n/d 25000000   # data\n").unwrap();
+        assert_eq!(fs.capacity(), 60_000_000);
+        assert_eq!(rows(&fs)[1], "/d\t25000000\t24933642\t66358");
+
+        assert_eq!(fs.mount_table("/a 10\n/nope 5"), Err(MountError::NotFound("/nope".to_string())));
+        assert_eq!(fs.mount_table("/a"), Err(MountError::Malformed { line: 1, text: "/a".to_string() }));
+        assert_eq!(fs.mount_table("\n/a lots"), Err(MountError::Malformed { line: 2, text: "/a lots".to_string() }));
+        assert_eq!(fs.volumes().len(), 3);
+    }
+
+    #[test]
+    fn removing_a_mount_point() {
+        let mut fs = parse_input(EXAMPLE);
+        fs.mount("/d", 1).unwrap();
+        fs.execute("rm -r /d").unwrap();
+        assert_eq!(fs.volumes().len(), 1);
+        assert_eq!(fs.volume(fs.root()).unwrap().used, fs.size());
+    }
+}